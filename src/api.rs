@@ -1,18 +1,197 @@
 use crate::todo::Todo;
+use crate::gh::GithubApi;
+use crate::gl::GitlabApi;
 use crate::config::Config;
-use crate::issue::{Issue, Issuer};
+use crate::index::OpenIssue;
+use crate::gitea::GiteaApi;
+use crate::local::LocalApi;
+use crate::util::RemoteUrl;
+use crate::template::IssueText;
+use crate::issue::{Issue, Issuer, IssueStates};
+use crate::settings::{Settings, Tracker};
+
+use std::path::Path;
+
+use clap::ValueEnum;
+
+/// Pull request of the `--branch` the changes were made on
+#[derive(Debug)]
+pub struct PullRequest<'a> {
+    /// Branch with the changes
+    pub head: &'a str,
+
+    /// Branch to merge them into
+    pub base: &'a str,
+
+    pub title: String,
+    pub body: String
+}
 
 #[async_trait::async_trait]
 pub trait Api: Send + Sync {
     fn get_api_token_env_var(&self) -> &str;
     fn get_api_token(&self) -> anyhow::Result<String>;
 
+    /// Trackers that don't need a token don't need a git remote either
+    #[inline(always)]
+    fn needs_token(&self) -> bool {
+        true
+    }
+
     fn get_project_url(&self, config: &Config) -> String;
     fn get_issues_api_url(&self, config: &Config) -> String;
     fn get_issue_api_url(&self, config: &Config, issue: &Issue) -> String;
 
-    fn make_client(&self, config: &Config) -> surf::Result<surf::Client>;
+    /// Link to `line` of `path` at commit `sha`, `None` if the tracker has no web ui
+    #[inline(always)]
+    fn get_permalink(&self, _config: &Config, _sha: &str, _path: &str, _line: u32) -> Option<String> {
+        None
+    }
+
+    fn make_client(&self) -> surf::Result<surf::Client>;
+
+    /// `text.location` (`path:line`) goes into a hidden marker in the body, see [`crate::index`]
+    ///
+    /// Returns: number of the created issue
+    async fn post_issue(&self, issuer: &Issuer, todo: &Todo, text: &IssueText) -> anyhow::Result<u64>;
+
+    /// All open issues (no pull requests), to not file the same TODO twice
+    async fn list_open_issues(&self, config: &Config) -> anyhow::Result<Vec<OpenIssue>>;
+    async fn check_if_issue_is_closed(&self, issuer: &Issuer, issue: &Issue) -> anyhow::Result<bool>;
+
+    /// States of many issues at once, in as few requests as the tracker allows.
+    /// Issues that couldn't be looked up are left out.
+    async fn get_issue_states(&self, config: &Config, numbers: &[u64]) -> anyhow::Result<IssueStates>;
+
+    /// Leaves `comment` on the issue and closes it
+    async fn close_issue(&self, issuer: &Issuer, issue: &Issue, comment: &str) -> anyhow::Result<()>;
+
+    /// Returns: url of the opened pull request, `None` if the tracker has no pull requests
+    async fn open_pull_request(&self, _config: &Config, _pr: &PullRequest) -> anyhow::Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+#[derive(Eq, Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub enum Backend {
+    Github,
+    Gitlab,
+
+    /// Gitea and Forgejo (e.g. Codeberg)
+    Gitea,
+
+    /// Markdown files under `.stalkr/issues`, no network needed
+    Local
+}
+
+impl Backend {
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::from_str(name, true).ok()
+    }
+
+    /// Guesses the issue tracker from the host of the git remote, defaulting to GitHub
+    #[must_use]
+    pub fn detect(host: &str, settings: &Settings) -> Self {
+        let is_configured = |url: Option<&str>| url.is_some_and(|url| is_url_on_host(url, host));
+
+        let has_label = |names: &[&str]| host.split('.').any(|label| names.contains(&label));
+
+        if is_configured(settings.github.url.as_deref()) {
+            Self::Github
+        } else if is_configured(settings.gitlab.url.as_deref()) || has_label(&["gitlab"]) {
+            Self::Gitlab
+        } else if is_configured(settings.gitea.url.as_deref())
+            || has_label(&["gitea", "forgejo", "codeberg"])
+        {
+            Self::Gitea
+        } else {
+            Self::Github
+        }
+    }
+}
+
+// the remote may be an ssh one, so the port is ignored
+fn is_url_on_host(url: &str, host: &str) -> bool {
+    let url = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let Some(url_host) = url.split('/').next() else { return false };
+
+    url_host.eq_ignore_ascii_case(host) || url_host
+        .split(':')
+        .next()
+        .is_some_and(|h| h.eq_ignore_ascii_case(host))
+}
+
+fn is_github_host(host: &str) -> bool {
+    let host = host.split(':').next().unwrap_or(host);
+    ["github.com", "www.github.com", "ssh.github.com"]
+        .iter()
+        .any(|h| h.eq_ignore_ascii_case(host))
+}
+
+/// Makes the api of `backend`, or of the backend detected from the git remote.
+/// Self-hosted GitLab and Gitea instances default to the host of the remote,
+/// GitHub Enterprise Server is only used when `github.url` is set, to not send
+/// the GitHub token to whatever host the repository was cloned from.
+pub fn make(
+    backend: Option<Backend>,
+    remote: Option<&RemoteUrl>,
+    settings: &Settings,
+    dir: &Path
+) -> anyhow::Result<Box<dyn Api>> {
+    let host = remote.map(|r| r.host.as_str());
+    let remote_base_url = remote.map(|r| r.base_url.as_str());
+
+    let backend = backend
+        .or(settings.backend)
+        .or_else(|| host.map(|host| Backend::detect(host, settings)))
+        .unwrap_or(Backend::Github);
+
+    let base_url = |tracker: &Tracker, default: Option<&str>| {
+        tracker.url.as_deref().map(ToOwned::to_owned)
+            .or_else(|| remote_base_url.map(ToOwned::to_owned))
+            .or_else(|| default.map(ToOwned::to_owned))
+    };
+
+    let api: Box<dyn Api> = match backend {
+        Backend::Github => {
+            let web_url = match (settings.github.url.as_deref(), host) {
+                (Some(url), _) => url.to_owned(),
+                (None, Some(host)) if !is_github_host(host) => anyhow::bail!{
+                    "the git remote is on {host}, which isn't github.com: set `github.url` in {} \
+                     if it's a GitHub Enterprise Server, or pick the tracker with --backend",
+                    Settings::FILE_NAME
+                },
+                (None, _) => GithubApi::DEFAULT_WEB_URL.to_owned()
+            };
+
+            Box::new(GithubApi::new(
+                &web_url,
+                settings.github.api_url.as_deref(),
+                settings.github.token_env.as_deref()
+            ))
+        }
+
+        Backend::Gitlab => {
+            let base_url = base_url(&settings.gitlab, Some(GitlabApi::DEFAULT_BASE_URL))
+                .unwrap_or_default();
+
+            Box::new(GitlabApi::new(&base_url, settings.gitlab.token_env.as_deref()))
+        }
+
+        Backend::Gitea => {
+            let Some(base_url) = base_url(&settings.gitea, None) else {
+                anyhow::bail!{
+                    "couldn't detect the Gitea url, set `gitea.url` in {}",
+                    Settings::FILE_NAME
+                }
+            };
+
+            Box::new(GiteaApi::new(&base_url, settings.gitea.token_env.as_deref()))
+        }
+
+        Backend::Local => Box::new(LocalApi::new(dir))
+    };
 
-    async fn post_issue(&self, issuer: &Issuer, todo: Todo);
-    async fn check_if_issue_is_closed(&self, issuer: &Issuer, issue: &Issue) -> bool;
+    Ok(api)
 }
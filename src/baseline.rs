@@ -0,0 +1,151 @@
+use crate::util;
+
+use std::fs;
+use std::path::Path;
+use std::fmt::Write;
+use std::collections::HashMap;
+
+use anyhow::{bail, Context};
+use rustc_hash::FxBuildHasher;
+use serde_json::{json, Value};
+
+/// Known TODO's that `stalkr check` shouldn't fail on, read from `.stalkr-baseline.json`.
+///
+/// ```json
+/// {
+///     "version": 1,
+///     "todos": [
+///         { "fingerprint": "9c1185a5c5e9fc54", "path": "src/main.rs", "title": "handle errors" }
+///     ]
+/// }
+/// ```
+///
+/// Only the fingerprint is used for matching, path and title are there to make diffs readable.
+#[derive(Debug, Default)]
+pub struct Baseline {
+    /// fingerprint -> how many times it occurs
+    counts: HashMap<Box<str>, usize, FxBuildHasher>,
+
+    entries: Vec<Entry>
+}
+
+#[derive(Debug)]
+struct Entry {
+    fingerprint: Box<str>,
+    path: Box<str>,
+    title: Box<str>
+}
+
+impl Baseline {
+    pub const FILE_NAME: &str = ".stalkr-baseline.json";
+
+    const VERSION: u64 = 1;
+
+    pub fn load(dir: &Path) -> anyhow::Result<Self> {
+        let path = dir.join(Self::FILE_NAME);
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e).with_context(|| format!("couldn't read {}", path.display()))
+        };
+
+        let json = serde_json::from_str::<Value>(&contents).with_context(|| {
+            format!("couldn't parse {}", path.display())
+        })?;
+
+        let version = json.get("version").and_then(Value::as_u64);
+        if version != Some(Self::VERSION) {
+            bail!("{}: unsupported baseline version: {version:?}", path.display())
+        }
+
+        let Some(todos) = json.get("todos").and_then(Value::as_array) else {
+            bail!("{}: `todos` must be an array", path.display())
+        };
+
+        let mut baseline = Self::default();
+
+        for todo in todos {
+            let Some(fingerprint) = todo.get("fingerprint").and_then(Value::as_str) else {
+                bail!("{}: every todo must have a `fingerprint` string", path.display())
+            };
+
+            let get = |key| todo.get(key).and_then(Value::as_str).unwrap_or_default();
+
+            baseline.insert(fingerprint.into(), get("path"), get("title"));
+        }
+
+        Ok(baseline)
+    }
+
+    pub fn save(&mut self, dir: &Path) -> anyhow::Result<()> {
+        let path = dir.join(Self::FILE_NAME);
+
+        // keep the file stable between updates
+        self.entries.sort_by(|a, b| {
+            (&a.path, &a.title, &a.fingerprint).cmp(&(&b.path, &b.title, &b.fingerprint))
+        });
+
+        let todos = self.entries.iter().map(|e| json!({
+            "fingerprint": e.fingerprint,
+            "path": e.path,
+            "title": e.title
+        })).collect::<Vec<_>>();
+
+        let json = json!({ "version": Self::VERSION, "todos": todos });
+
+        let mut contents = serde_json::to_string_pretty(&json)?;
+        contents.push('\n');
+
+        fs::write(&path, contents).with_context(|| format!("couldn't write {}", path.display()))
+    }
+
+    /// Fingerprint of a TODO that survives line shifts and whitespace/case edits of the title.
+    #[must_use]
+    pub fn fingerprint(path: &str, title: &str) -> Box<str> {
+        // FNV-1a, the fingerprints are committed so the hash must never change
+        const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+        const PRIME:  u64 = 0x0000_0100_0000_01b3;
+
+        let title = title.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+
+        let hash = path.bytes()
+            .chain([0])
+            .chain(title.bytes())
+            .fold(OFFSET, |h, b| (h ^ u64::from(b)).wrapping_mul(PRIME));
+
+        let mut s = String::with_capacity(16);
+        _ = write!(s, "{hash:016x}");
+        util::string_into_boxed_str_norealloc(s)
+    }
+
+    #[inline]
+    pub fn insert(&mut self, fingerprint: Box<str>, path: &str, title: &str) {
+        *self.counts.entry(fingerprint.clone()).or_default() += 1;
+        self.entries.push(Entry { fingerprint, path: path.into(), title: title.into() });
+    }
+
+    /// Consumes one occurrence of `fingerprint`, so duplicated TODO's are only
+    /// covered as many times as they appear in the baseline.
+    ///
+    /// Returns: whether the TODO is known
+    #[inline]
+    pub fn take(&mut self, fingerprint: &str) -> bool {
+        match self.counts.get_mut(fingerprint) {
+            Some(count) if *count > 0 => { *count -= 1; true }
+            _ => false
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
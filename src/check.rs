@@ -0,0 +1,249 @@
+use crate::util;
+use crate::todo::Todo;
+use crate::baseline::Baseline;
+use crate::config::Config;
+use crate::fm::FileManager;
+use crate::mode::ModeValue;
+use crate::prompt::ListValue;
+
+use std::sync::Arc;
+use std::sync::atomic::{Ordering, AtomicUsize};
+
+use anyhow::{bail, Context};
+use serde_json::Value;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+/// Rules that `stalkr check` enforces
+#[derive(Debug, Clone)]
+pub struct Policy {
+    /// Fail on TODO's that aren't linked to an issue yet
+    pub deny_untagged: bool,
+
+    /// Fail on tags whose issue number couldn't be parsed, e.g. `TODO(#abc):`
+    pub deny_malformed: bool,
+
+    /// Maximum number of TODO's in a directory (recursively), e.g. `("src/legacy", 40)`.
+    /// The root directory is the empty string. TODO's recorded in the baseline don't count.
+    pub budgets: Vec<(String, usize)>
+}
+
+impl Default for Policy {
+    #[inline]
+    fn default() -> Self {
+        Self { deny_untagged: true, deny_malformed: true, budgets: Vec::new() }
+    }
+}
+
+impl Policy {
+    pub fn from_json(json: &Value) -> anyhow::Result<Self> {
+        let mut policy = Self::default();
+
+        let get_bool = |key: &str| -> anyhow::Result<Option<bool>> {
+            json.get(key).map(|v| {
+                v.as_bool().with_context(|| format!("`check.{key}` must be a boolean"))
+            }).transpose()
+        };
+
+        if let Some(deny) = get_bool("deny_untagged")?  { policy.deny_untagged  = deny }
+        if let Some(deny) = get_bool("deny_malformed")? { policy.deny_malformed = deny }
+
+        if let Some(budgets) = json.get("budgets") {
+            let Some(budgets) = budgets.as_object() else {
+                bail!("`check.budgets` must be an object of directory -> max count")
+            };
+
+            for (dir, max) in budgets {
+                let Some(max) = max.as_u64() else {
+                    bail!("budget of {dir:?} must be a non-negative integer")
+                };
+
+                policy.budgets.push((Self::normalize_dir(dir), max as usize));
+            }
+        }
+
+        Ok(policy)
+    }
+
+    /// Parses a `DIR=N` budget, as passed on the command line
+    pub fn parse_budget(s: &str) -> anyhow::Result<(String, usize)> {
+        let Some((dir, max)) = s.rsplit_once('=') else {
+            bail!("invalid budget {s:?}, expected DIR=N")
+        };
+
+        let max = max.trim().parse().with_context(|| format!("invalid budget count in {s:?}"))?;
+
+        Ok((Self::normalize_dir(dir), max))
+    }
+
+    // `./src/` -> `src`, `.` -> ``
+    #[inline]
+    fn normalize_dir(dir: &str) -> String {
+        let dir = dir.trim().trim_start_matches("./").trim_end_matches('/');
+        if dir == "." { String::new() } else { dir.to_owned() }
+    }
+}
+
+struct Entry {
+    path: String,
+    todo: Todo
+}
+
+/// Collects every TODO of the tree and checks them against the [`Policy`], with no network access.
+pub struct Checker {
+    pub fm: Arc<FileManager>,
+    pub config: Arc<Config>,
+    pub processed_count: Arc<AtomicUsize>,
+    pub baseline: Baseline,
+
+    entries: Vec<Entry>
+}
+
+impl Checker {
+    #[inline]
+    #[must_use]
+    pub const fn new(
+        fm: Arc<FileManager>,
+        config: Arc<Config>,
+        processed_count: Arc<AtomicUsize>,
+        baseline: Baseline
+    ) -> Self {
+        Self { fm, config, processed_count, baseline, entries: Vec::new() }
+    }
+
+    /// Prints `path:line: message` diagnostics for every violation,
+    /// or rewrites the baseline if `config.update_baseline` is set.
+    ///
+    /// Returns: number of violations
+    pub async fn run(&mut self, mut rx: UnboundedReceiver<ListValue>) -> anyhow::Result<usize> {
+        while let Some(list_value) = rx.recv().await {
+            let ModeValue::Checking(todos) = list_value.mode_value else {
+                unreachable!("checker only receives todoʼs to check")
+            };
+
+            let Some(file_id) = todos.first().map(|t| t.loc.file_id()) else {
+                continue
+            };
+
+            let path = util::relative_path(
+                &self.config.cwd,
+                &self.fm.get_file_path_unchecked(file_id)
+            );
+
+            self.processed_count.fetch_add(todos.len(), Ordering::SeqCst);
+
+            self.entries.extend(todos.into_iter().map(|todo| {
+                Entry { path: path.clone(), todo }
+            }));
+        }
+
+        // files are scanned in parallel, so sort to get a stable output
+        self.entries.sort_by(|a, b| {
+            (&a.path, a.todo.loc.line_number()).cmp(&(&b.path, b.todo.loc.line_number()))
+        });
+
+        if self.config.update_baseline {
+            self.update_baseline()?;
+            return Ok(0)
+        }
+
+        let diagnostics = self.check();
+
+        for diagnostic in &diagnostics {
+            println!("{diagnostic}");
+        }
+
+        Ok(diagnostics.len())
+    }
+
+    // every TODO that the policy could complain about, regardless of what the policy is now
+    #[inline]
+    const fn needs_attention(todo: &Todo) -> bool {
+        !todo.is_tagged || todo.issue_number.is_none()
+    }
+
+    fn update_baseline(&mut self) -> anyhow::Result<()> {
+        let mut baseline = Baseline::default();
+
+        for Entry { path, todo } in &self.entries {
+            if Self::needs_attention(todo) {
+                baseline.insert(Baseline::fingerprint(path, &todo.title), path, &todo.title);
+            }
+        }
+
+        baseline.save(&self.config.cwd)?;
+
+        println!{
+            "[{n} todoʼs recorded in {f}]",
+            n = baseline.len(),
+            f = Baseline::FILE_NAME
+        };
+
+        self.baseline = baseline;
+
+        Ok(())
+    }
+
+    fn check(&mut self) -> Vec<String> {
+        let policy = &self.config.settings.check;
+
+        let mut diagnostics = Vec::new();
+        let mut baselined = 0;
+
+        // what the budgets are checked against
+        let mut counted = Vec::with_capacity(self.entries.len());
+
+        for Entry { path, todo } in &self.entries {
+            let line = todo.loc.line_number();
+
+            if !Self::needs_attention(todo) {
+                counted.push(path);
+                continue
+            }
+
+            if self.baseline.take(&Baseline::fingerprint(path, &todo.title)) {
+                baselined += 1;
+                continue
+            }
+
+            counted.push(path);
+
+            if todo.is_tagged {
+                if policy.deny_malformed {
+                    diagnostics.push(format!{
+                        "{path}:{line}: error: malformed {k} tag: {p}",
+                        k = todo.keyword,
+                        p = todo.preview
+                    });
+                }
+            } else if policy.deny_untagged {
+                diagnostics.push(format!{
+                    "{path}:{line}: error: untagged {k}: {t}",
+                    k = todo.keyword,
+                    t = todo.title
+                });
+            }
+        }
+
+        for (dir, max) in &policy.budgets {
+            let count = counted.iter().filter(|path| Self::is_in_dir(path, dir)).count();
+
+            if count > *max {
+                let dir = if dir.is_empty() { "." } else { dir };
+                diagnostics.push(format!{
+                    "{dir}/: error: {count} todoʼs, over the budget of {max}"
+                });
+            }
+        }
+
+        if baselined != 0 {
+            eprintln!("[{baselined} known todoʼs skipped, see {}]", Baseline::FILE_NAME);
+        }
+
+        diagnostics
+    }
+
+    #[inline]
+    fn is_in_dir(path: &str, dir: &str) -> bool {
+        dir.is_empty() || path.strip_prefix(dir).is_some_and(|rest| rest.starts_with('/'))
+    }
+}
@@ -1,4 +1,7 @@
 use crate::mode::Mode;
+use crate::api::Backend;
+use crate::git::DirtyFiles;
+use crate::export::Format;
 
 use std::path::PathBuf;
 
@@ -21,6 +24,14 @@ pub struct Cli {
     #[clap(long, requires = "owner", global = true)]
     pub repository: Option<String>,
 
+    /// Keywords to look for, e.g. `TODO,FIXME,HACK` (overrides the ones from `.stalkr.json`)
+    #[clap(long, value_delimiter = ',', global = true)]
+    pub keywords: Vec<String>,
+
+    /// Issue tracker to use (detected from the git remote by default)
+    #[clap(long, value_enum, global = true)]
+    pub backend: Option<Backend>,
+
     #[clap(subcommand)]
     pub command: Option<Commands>,
 }
@@ -33,11 +44,40 @@ impl Cli {
     pub fn remote(&self) -> &str {
         match &self.command {
             Some(Commands::Purge { remote, .. })  => remote,
+            Some(Commands::Close { remote, .. })  => remote,
             Some(Commands::Report { remote, .. }) => remote,
             _ => Self::DEFAULT_REMOTE
         }
     }
 
+    /// Branch to make the changes on and open a pull request from
+    #[inline(always)]
+    #[must_use]
+    pub fn branch(&self) -> Option<&str> {
+        match &self.command {
+            Some(Commands::Purge { branch, .. })  => branch.as_deref(),
+            Some(Commands::Report { branch, .. }) => branch.as_deref(),
+            _ => None
+        }
+    }
+
+    #[inline(always)]
+    #[must_use]
+    pub const fn dirty_files(&self) -> DirtyFiles {
+        match &self.command {
+            Some(Commands::Report { allow_dirty: true, .. } | Commands::Purge { allow_dirty: true, .. }) => {
+                DirtyFiles::Allow
+            }
+
+            Some(
+                Commands::Report { stage_own_changes: true, .. } |
+                Commands::Purge { stage_own_changes: true, .. }
+            ) => DirtyFiles::StageOwnChanges,
+
+            _ => DirtyFiles::Skip
+        }
+    }
+
     #[inline(always)]
     #[must_use]
     pub fn simulate(&self) -> bool {
@@ -47,12 +87,45 @@ impl Cli {
         }
     }
 
+    #[inline(always)]
+    #[must_use]
+    pub const fn list_format(&self) -> Option<Format> {
+        match &self.command {
+            Some(Commands::List { format, .. }) => *format,
+            _ => None
+        }
+    }
+
+    #[inline(always)]
+    #[must_use]
+    pub fn output(&self) -> Option<&PathBuf> {
+        match &self.command {
+            Some(Commands::List { output, .. }) => output.as_ref(),
+            _ => None
+        }
+    }
+
+    #[inline(always)]
+    #[must_use]
+    pub const fn update_baseline(&self) -> bool {
+        matches!(self.command, Some(Commands::Baseline { action: BaselineAction::Update }))
+    }
+
+    #[inline(always)]
+    #[must_use]
+    pub const fn use_baseline(&self) -> bool {
+        !matches!(self.command, Some(Commands::Check { no_baseline: true, .. }))
+    }
+
     #[inline(always)]
     #[must_use]
     pub const fn mode(&self) -> Mode {
         match &self.command {
-            Some(Commands::List { .. })  => Mode::Listing,
-            Some(Commands::Purge { .. }) => Mode::Purging,
+            Some(Commands::List { .. })     => Mode::Listing,
+            Some(Commands::Purge { .. })    => Mode::Purging,
+            Some(Commands::Close { .. })    => Mode::Closing,
+            Some(Commands::Check { .. })    => Mode::Checking,
+            Some(Commands::Baseline { .. }) => Mode::Checking,
             _ => Mode::Reporting
         }
     }
@@ -71,6 +144,14 @@ pub enum Commands {
         /// Show only reported TODOs
         #[clap(long, conflicts_with = "unreported")]
         reported: bool,
+
+        /// Print TODOs in a machine-readable format instead of prompting
+        #[clap(long, value_enum)]
+        format: Option<Format>,
+
+        /// Write the output to a file instead of stdout
+        #[clap(long, short = 'o', requires = "format")]
+        output: Option<PathBuf>,
     },
 
     /// Reports all TODOs as GitHub issues
@@ -87,6 +168,18 @@ pub enum Commands {
         #[clap(long, default_value = Cli::DEFAULT_REMOTE)]
         remote: String,
 
+        /// Insert the tags on a new branch, push it to `--remote` and open a pull request
+        #[clap(long, value_name = "NAME")]
+        branch: Option<String>,
+
+        /// Also edit files with uncommitted changes, committing them as a whole
+        #[clap(long, conflicts_with = "stage_own_changes")]
+        allow_dirty: bool,
+
+        /// Also edit files with uncommitted changes, committing only the inserted tags
+        #[clap(long)]
+        stage_own_changes: bool,
+
         #[clap(
             long,
             default_value = "false",
@@ -95,10 +188,82 @@ pub enum Commands {
         simulate: bool,
     },
 
+    /// Fails if the TODOs violate the policy
+    #[clap(about = "Checks TODO comments against a policy, without any network access")]
+    Check {
+        /// Don't fail on TODOs that aren't reported yet
+        #[clap(long)]
+        allow_untagged: bool,
+
+        /// Maximum number of TODOs in a directory, not counting the ones in the baseline,
+        /// e.g. `src=20` (can be repeated)
+        #[clap(long, value_name = "DIR=N")]
+        budget: Vec<String>,
+
+        /// Also report TODOs that are recorded in the baseline file
+        #[clap(long)]
+        no_baseline: bool,
+    },
+
+    /// Manages the baseline of known TODOs that `check` doesn't fail on
+    #[clap(about = "Manages the baseline of known TODOs that `check` ignores")]
+    Baseline {
+        #[clap(subcommand)]
+        action: BaselineAction,
+    },
+
+    /// Manages the issues of the local tracker (`--backend local`)
+    #[clap(about = "Manages issues of the local tracker in .stalkr/issues")]
+    Issue {
+        #[clap(subcommand)]
+        action: IssueAction,
+    },
+
     /// Removes all reported TODOs that refer to closed issues
     #[clap(about = "Removes TODO comments linked to closed GitHub issues")]
     Purge {
         #[clap(long, default_value = Cli::DEFAULT_REMOTE)]
         remote: String,
+
+        /// Remove the TODOs on a new branch, push it to `--remote` and open a pull request
+        #[clap(long, value_name = "NAME")]
+        branch: Option<String>,
+
+        /// Also edit files with uncommitted changes, committing them as a whole
+        #[clap(long, conflicts_with = "stage_own_changes")]
+        allow_dirty: bool,
+
+        /// Also edit files with uncommitted changes, committing only the removed TODOs
+        #[clap(long)]
+        stage_own_changes: bool,
+    },
+
+    /// Closes issues created by stalkr whose TODOs were deleted from the code
+    #[clap(about = "Closes issues whose TODO comments were removed")]
+    Close {
+        #[clap(long, default_value = Cli::DEFAULT_REMOTE)]
+        remote: String,
     }
 }
+
+#[derive(Subcommand)]
+pub enum BaselineAction {
+    /// Records all current untagged and malformed TODOs in the baseline file
+    #[clap(about = "Records all current untagged and malformed TODOs in the baseline file")]
+    Update,
+}
+
+#[derive(Subcommand)]
+pub enum IssueAction {
+    /// Marks a local issue as closed, so `purge` removes its TODOs
+    #[clap(about = "Closes a local issue")]
+    Close {
+        number: u64,
+    },
+
+    /// Marks a local issue as open again
+    #[clap(about = "Reopens a local issue")]
+    Reopen {
+        number: u64,
+    },
+}
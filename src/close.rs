@@ -0,0 +1,111 @@
+use crate::git;
+use crate::util;
+use crate::config::Config;
+use crate::todo::Description;
+use crate::fm::FileManager;
+use crate::index::OpenIssue;
+
+use std::fs;
+use std::collections::HashSet;
+
+use rustc_hash::FxBuildHasher;
+
+/// Open issue created by stalkr whose tag isn't anywhere in the tree anymore
+#[derive(Debug)]
+pub struct Orphan {
+    pub issue: OpenIssue,
+
+    /// Where the TODO was and what removed it, shown when prompting
+    pub description: Description,
+
+    /// Left on the issue when closing it
+    pub comment: String
+}
+
+impl Orphan {
+    #[must_use]
+    pub fn new(config: &Config, issue: OpenIssue) -> Self {
+        let path = issue.location.as_deref().map(|location| {
+            location.rsplit_once(':').map_or(location, |(path, _)| path)
+        });
+
+        let needle = format!("(#{n})", n = issue.number);
+        let commit = git::find_removing_commit(&config.cwd, &needle, path);
+
+        let mut lines = Vec::with_capacity(2);
+
+        if let Some(location) = &issue.location {
+            lines.push(format!("was at {location}"));
+        }
+
+        let comment = if let Some(git::Commit { sha, subject }) = &commit {
+            lines.push(format!("removed in {sha} {subject}"));
+            format!("The TODO of this issue was removed in {sha} ({subject}).")
+        } else {
+            "The TODO of this issue is no longer in the code.".to_owned()
+        };
+
+        let description = Description {
+            lines: lines.into_iter().map(util::string_into_boxed_str_norealloc).collect()
+        };
+
+        Self { issue, description, comment }
+    }
+}
+
+/// Open issues created by stalkr that none of the `tagged` issue numbers refer to,
+/// and whose TODO is known to be gone: its file was deleted, or was scanned without the tag in it.
+/// An issue whose file wasn't scanned (e.g. ignored, or outside of the scanned directory)
+/// or that has no location marker is skipped, its TODO may well still be there.
+#[must_use]
+pub fn find_orphans(
+    config: &Config,
+    fm: &FileManager,
+    tagged: &HashSet<u64, FxBuildHasher>,
+    issues: Vec<OpenIssue>
+) -> Vec<Orphan> {
+    let default_labels = &config.settings.issues.labels;
+
+    let mut orphans = issues.into_iter()
+        .filter(|issue| issue.is_created_by_stalkr(default_labels))
+        .filter(|issue| !tagged.contains(&issue.number))
+        .filter(|issue| is_todo_gone(config, fm, issue))
+        .map(|issue| Orphan::new(config, issue))
+        .collect::<Vec<_>>();
+
+    orphans.sort_by_key(|o| o.issue.number);
+    orphans
+}
+
+fn is_todo_gone(config: &Config, fm: &FileManager, issue: &OpenIssue) -> bool {
+    let n = issue.number;
+
+    let Some(location) = &issue.location else {
+        eprintln!("[#{n}: no location marker, so there's no telling where its TODO was, skipping it]");
+        return false
+    };
+
+    let path = location.rsplit_once(':').map_or(&**location, |(path, _)| path);
+    let full_path = config.cwd.join(path);
+
+    if !full_path.exists() {
+        return true
+    }
+
+    if !fm.was_scanned(&full_path) {
+        eprintln!("[#{n}: {path} wasn't scanned, skipping it]");
+        return false
+    }
+
+    // the tag is still there, with a keyword that wasn't looked for
+    let needle = format!("(#{n})");
+    let is_still_tagged = fs::read(&full_path).is_ok_and(|contents| {
+        memchr::memmem::find(&contents, needle.as_bytes()).is_some()
+    });
+
+    if is_still_tagged {
+        eprintln!("[#{n}: still tagged in {path} with a keyword that wasn't looked for, skipping it]");
+    }
+
+    !is_still_tagged
+}
@@ -1,36 +1,66 @@
-use std::mem;
+use crate::syntax;
 
-#[repr(u8)]
-#[derive(Copy, Clone)]
+#[derive(Eq, Copy, Clone, Debug, PartialEq)]
 pub enum Comment {
-    Hash  = b'#',
-    Dash  = b'-',
-    Slash = b'/',
+    /// Comment that runs until the end of the line, e.g. `//`
+    Line(&'static str),
+
+    /// Comment delimited by an opener and a closer, e.g. `/*` and `*/`
+    Block {
+        open  : &'static str,
+        close : &'static str
+    }
 }
 
 impl Comment {
     #[inline(always)]
-    #[must_use] 
-    pub const fn from_u8_unchecked(byte: u8) -> Self {
-        unsafe { mem::transmute(byte) }
+    #[must_use]
+    pub const fn opener(&self) -> &'static str {
+        match self {
+            Self::Line(marker)       => marker,
+            Self::Block { open, .. } => open
+        }
+    }
+
+    #[inline(always)]
+    #[must_use]
+    pub const fn closer(&self) -> Option<&'static str> {
+        match self {
+            Self::Line(_)             => None,
+            Self::Block { close, .. } => Some(close)
+        }
     }
 
+    /// Returns the offset right after the line comment marker, if `h_` is a line comment
+    /// of this kind (after optional leading whitespace).
     #[inline]
-    #[must_use] 
+    #[must_use]
     pub fn is_line_a_comment(&self, h_: &str) -> Option<usize> {
-        let h = h_.trim_start().as_bytes();
+        let Self::Line(marker) = self else { return None };
 
-        let first_byte = h.first()?;
+        let h = h_.trim_start();
 
-        let second_byte = || h.get(1);
+        if !syntax::is_marker_at(marker, None, h.as_bytes()) {
+            return None
+        }
+
+        Some(h_.len() - h.len() + marker.len())
+    }
+
+    /// Returns the offset of the content of a line that lives inside of a block comment,
+    /// skipping leading whitespace and an optional decorative `*` (as in ` * foo`).
+    #[inline]
+    #[must_use]
+    pub fn block_line_content_start(&self, h_: &str) -> usize {
+        let h = h_.trim_start();
 
-        let comment_offset = match (*self as _, first_byte) {
-            (b'#', b'#') => 1,
-            (b'/', b'/') if matches!(second_byte()?, b'/') => 2,
-            (b'-', b'-') if matches!(second_byte()?, b'-') => 2,
-            _ => return None
+        let h = match self {
+            Self::Block { close, .. } if !h.starts_with(close) => {
+                h.strip_prefix('*').map_or(h, str::trim_start)
+            }
+            _ => h
         };
 
-        Some(h_.len() - h.len() + comment_offset)
+        h_.len() - h.len()
     }
 }
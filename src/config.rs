@@ -1,10 +1,17 @@
 use crate::util;
-use crate::cli::Cli;
-use crate::api::Api;
+use crate::cli::{Cli, Commands};
+use crate::api::{self, Api};
 use crate::mode::Mode;
-use crate::git::GitLocker;
-
-use std::sync::Arc;
+use crate::http::Http;
+use crate::git::{Repo, GitHead, GitLocker};
+use crate::check::Policy;
+use crate::export::Format;
+use crate::index::IssueIndex;
+use crate::issue::IssueStates;
+use crate::settings::Settings;
+use crate::keyword::{Keyword, Keywords};
+
+use std::sync::{Arc, Mutex, OnceLock};
 use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
 
@@ -18,18 +25,58 @@ pub struct Config {
 
     pub api: Box<dyn Api>,
 
+    pub http: Http,
+
     pub git_locker: Arc<GitLocker>,
 
+    /// Where the tree is at, for issue templates and as the base of the pull request.
+    /// Only read when reporting or with `--branch`.
+    pub head: GitHead,
+
+    /// Remote to push `branch` to
+    pub remote: Box<str>,
+
+    /// Branch to make the changes on, see [`crate::cli::Cli::branch`]
+    pub branch: Option<Box<str>>,
+
+    /// Issues whose tags were inserted or removed during the run, listed in the pull request
+    pub tagged_issues: Mutex<Vec<(u64, Box<str>)>>,
+
+    pub settings: Settings,
+
     pub simulate_reporting: bool,
 
-    pub found_closed_todo: AtomicBool
+    pub list_format: Option<Format>,
+    pub output: Option<PathBuf>,
+
+    pub update_baseline: bool,
+    pub use_baseline: bool,
+
+    pub found_closed_todo: AtomicBool,
+
+    /// Open issues of the tracker, fetched before reporting to not file duplicates
+    pub issue_index: OnceLock<IssueIndex>,
+
+    /// States of the issues that tags refer to, fetched in bulk before purging
+    pub issue_states: OnceLock<IssueStates>
 }
 
 impl Config {
     pub fn new(cli: &Cli) -> anyhow::Result::<Self> {
-        let api = Box::new(crate::gh::GithubApi);
+        let mut settings = Settings::load(&cli.directory)?;
+
+        let remote = util::get_git_origin_url(
+            &cli.directory,
+            cli.remote()
+        ).as_deref().and_then(util::parse_remote_url);
 
-        let token = if cli.mode() == Mode::Listing {
+        let api = api::make(cli.backend, remote.as_ref(), &settings, &cli.directory)?;
+
+        let http = Http::new(api.make_client().map_err(|e| {
+            anyhow::anyhow!("failed to build API client: {e}")
+        })?);
+
+        let token = if cli.mode().is_offline() || !api.needs_token() {
             None
         } else {
             let Ok(token) = api.get_api_token() else {
@@ -44,20 +91,19 @@ impl Config {
             Some(token)
         };
 
-        let remote = cli.remote();
-
         let (owner, repo) = if let (Some(owner), Some(repo)) = (
             &cli.owner, &cli.repository
         ) {
             (owner.to_owned(), repo.to_owned())
         } else {
-            match util::get_git_origin_url(
-                cli.directory.clone(),
-                remote
-            ).as_deref().and_then(util::parse_owner_repo) {
-                Some(x) => x,
+            match remote {
+                Some(util::RemoteUrl { owner, repo, .. }) => (owner, repo),
+
+                // checking and local trackers don't need to know about the project
+                None if cli.mode() == Mode::Checking || !api.needs_token() => Default::default(),
+
                 None => return Err(anyhow::anyhow!{
-                    "couldn't detect Github owner/repo"
+                    "couldn't detect owner/repo from the git remote"
                 })
             }
         };
@@ -72,9 +118,53 @@ impl Config {
 
         let simulate_reporting = cli.simulate();
 
+        let list_format = cli.list_format();
+        let output = cli.output().cloned();
+
+        let update_baseline = cli.update_baseline();
+        let use_baseline = cli.use_baseline();
+
         let found_closed_todo = AtomicBool::new(false);
 
-        let git_locker = Arc::new(GitLocker::new());
+        let git_locker = Arc::new(GitLocker::new(
+            settings.commit,
+            cli.dirty_files(),
+            Repo::discover(&cwd)
+        ));
+
+        let branch = cli.branch().map(Into::into);
+
+        let head = if mode == Mode::Reporting || branch.is_some() {
+            GitHead::read(&cwd)
+        } else {
+            GitHead::default()
+        };
+
+        if !cli.keywords.is_empty() {
+            if let Some(name) = cli.keywords.iter().find(|n| !Keyword::is_valid_name(n)) {
+                return Err(anyhow::anyhow!("invalid keyword name: {name:?}"))
+            }
+
+            // keep the labels and the title prefix from the settings
+            let keywords = cli.keywords.iter().map(|name| {
+                settings.keywords.get(name).map_or_else(
+                    || Keyword::new(name),
+                    |keyword| (**keyword).clone()
+                )
+            }).collect();
+
+            settings.keywords = Keywords::new(keywords);
+        }
+
+        if let Some(Commands::Check { allow_untagged, budget, .. }) = &cli.command {
+            if *allow_untagged {
+                settings.check.deny_untagged = false;
+            }
+
+            for budget in budget {
+                settings.check.budgets.push(Policy::parse_budget(budget)?);
+            }
+        }
 
         Ok(Self {
             owner,
@@ -83,9 +173,21 @@ impl Config {
             cwd,
             mode,
             api,
+            http,
             git_locker,
+            head,
+            remote: cli.remote().into(),
+            branch,
+            tagged_issues: Mutex::new(Vec::new()),
+            settings,
             simulate_reporting,
+            list_format,
+            output,
+            update_baseline,
+            use_baseline,
             found_closed_todo,
+            issue_index: OnceLock::new(),
+            issue_states: OnceLock::new(),
         })
     }
 
@@ -0,0 +1,220 @@
+use crate::util;
+use crate::sarif;
+use crate::todo::Todo;
+use crate::config::Config;
+use crate::fm::FileManager;
+use crate::mode::ModeValue;
+use crate::prompt::ListValue;
+
+use std::fs::File;
+use std::sync::Arc;
+use std::path::Path;
+use std::io::{self, BufWriter, Write};
+use std::sync::atomic::{Ordering, AtomicUsize};
+
+use clap::ValueEnum;
+use serde_json::Value;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+#[derive(Eq, Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub enum Format {
+    /// A single JSON array
+    Json,
+
+    /// One JSON object per line
+    Ndjson,
+
+    /// Comma-separated values with a header row
+    Csv,
+
+    /// SARIF 2.1.0 log, for code-scanning viewers
+    Sarif,
+}
+
+impl Format {
+    // formats that can be written out as soon as a file is scanned
+    #[inline(always)]
+    #[must_use]
+    pub const fn is_streaming(&self) -> bool {
+        matches!(self, Self::Ndjson | Self::Csv)
+    }
+}
+
+/// Writes listed TODO's out in a machine-readable [`Format`], with no interactive prompts.
+pub struct Exporter {
+    pub fm: Arc<FileManager>,
+    pub config: Arc<Config>,
+    pub format: Format,
+    pub processed_count: Arc<AtomicUsize>,
+
+    out: Box<dyn Write + Send>,
+
+    // records of non-streaming formats are collected and written out at the end
+    records: Vec<Record>
+}
+
+pub struct Record {
+    pub path: String,
+    pub line: u32,
+    pub keyword: Box<str>,
+    pub title: Box<str>,
+    pub description: Box<[Box<str>]>,
+    pub issue_number: Option<u64>,
+    pub is_tagged: bool,
+}
+
+impl Record {
+    const CSV_HEADER: &str = "path,line,keyword,title,description,issue,tagged";
+
+    #[inline]
+    #[must_use]
+    pub fn new(todo: &Todo, path: String) -> Self {
+        Self {
+            path,
+            line: todo.loc.line_number(),
+            keyword: todo.keyword.name.clone(),
+            title: todo.title.clone(),
+            description: todo.description.as_ref().map(|d| d.lines.clone()).unwrap_or_default(),
+            issue_number: todo.issue_number,
+            is_tagged: todo.is_tagged,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn as_json_value(&self) -> Value {
+        serde_json::json!({
+            "path": self.path,
+            "line": self.line,
+            "keyword": self.keyword,
+            "title": self.title,
+            "description": self.description,
+            "issue": self.issue_number,
+            "tagged": self.is_tagged
+        })
+    }
+
+    pub fn write_csv(&self, w: &mut impl Write) -> io::Result<()> {
+        let line = self.line.to_string();
+        let description = self.description.join("\n");
+        let issue = self.issue_number.map(|n| n.to_string()).unwrap_or_default();
+        let tagged = if self.is_tagged { "true" } else { "false" };
+
+        let fields = [
+            &*self.path,
+            &line,
+            &self.keyword,
+            &self.title,
+            &description,
+            &issue,
+            tagged
+        ];
+
+        for (i, field) in fields.into_iter().enumerate() {
+            if i > 0 { w.write_all(b",")? }
+            Self::write_csv_field(w, field)?;
+        }
+
+        w.write_all(b"\n")
+    }
+
+    // RFC 4180: quote fields containing separators, quotes or newlines and double the quotes
+    fn write_csv_field(w: &mut impl Write, field: &str) -> io::Result<()> {
+        if !field.contains([',', '"', '\n', '\r']) {
+            return w.write_all(field.as_bytes())
+        }
+
+        write!(w, "\"{f}\"", f = field.replace('"', "\"\""))
+    }
+}
+
+impl Exporter {
+    pub fn new(
+        fm: Arc<FileManager>,
+        config: Arc<Config>,
+        format: Format,
+        output: Option<&Path>,
+        processed_count: Arc<AtomicUsize>
+    ) -> io::Result<Self> {
+        let out: Box<dyn Write + Send> = match output {
+            Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+            None       => Box::new(BufWriter::new(io::stdout()))
+        };
+
+        Ok(Self { fm, config, format, processed_count, out, records: Vec::new() })
+    }
+
+    /// Spawn the exporting loop and return its `JoinHandle`.
+    #[must_use]
+    pub fn spawn(
+        self,
+        rx: UnboundedReceiver<ListValue>
+    ) -> tokio::task::JoinHandle<()> {
+        let mut me = self;
+        tokio::spawn(async move {
+            if let Err(e) = me.run(rx).await {
+                eprintln!("[could not export todoʼs]: {e}");
+            }
+        })
+    }
+
+    pub async fn run(&mut self, mut rx: UnboundedReceiver<ListValue>) -> io::Result<()> {
+        if self.format == Format::Csv {
+            writeln!(self.out, "{h}", h = Record::CSV_HEADER)?;
+        }
+
+        while let Some(list_value) = rx.recv().await {
+            let ModeValue::Listing(todos) = list_value.mode_value else {
+                unreachable!("exporter only receives listed todoʼs")
+            };
+
+            let Some(file_id) = todos.first().map(|t| t.loc.file_id()) else {
+                continue
+            };
+
+            let path = util::relative_path(
+                &self.config.cwd,
+                &self.fm.get_file_path_unchecked(file_id)
+            );
+
+            for todo in &todos {
+                let record = Record::new(todo, path.clone());
+
+                match self.format {
+                    Format::Ndjson => {
+                        serde_json::to_writer(&mut self.out, &record.as_json_value())?;
+                        self.out.write_all(b"\n")?;
+                    }
+                    Format::Csv => record.write_csv(&mut self.out)?,
+                    Format::Json | Format::Sarif => self.records.push(record)
+                }
+            }
+
+            self.processed_count.fetch_add(todos.len(), Ordering::SeqCst);
+        }
+
+        if !self.format.is_streaming() {
+            // files are scanned in parallel, so sort to get a stable output
+            self.records.sort_by(|a, b| (&a.path, a.line).cmp(&(&b.path, b.line)));
+            self.write_collected()?;
+        }
+
+        self.out.flush()
+    }
+
+    fn write_collected(&mut self) -> io::Result<()> {
+        match self.format {
+            Format::Json => {
+                let records = self.records.iter().map(Record::as_json_value).collect();
+                serde_json::to_writer_pretty(&mut self.out, &Value::Array(records))?;
+                self.out.write_all(b"\n")
+            }
+            Format::Sarif => {
+                let log = sarif::make_log(&self.records, &self.config.settings.keywords);
+                serde_json::to_writer_pretty(&mut self.out, &log)?;
+                self.out.write_all(b"\n")
+            }
+            Format::Ndjson | Format::Csv => Ok(())
+        }
+    }
+}
@@ -2,14 +2,17 @@ use crate::tag::Tag;
 
 use std::hint;
 use std::path::Path;
-use std::io::{self, Read};
-use std::fs::{self, File};
+use std::ops::Range;
+use std::hash::Hasher;
+use std::io::{self, Read, Write};
+use std::fs::{self, File, OpenOptions};
 use std::sync::atomic::{AtomicU32, Ordering};
 
-use rustc_hash::FxBuildHasher;
+use anyhow::{bail, Context};
+use rustc_hash::{FxHasher, FxBuildHasher};
 use dashmap::{DashMap, DashSet};
-use memmap2::{MmapMut, MmapOptions};
-use dashmap::mapref::one::{Ref, RefMut, MappedRef, MappedRefMut};
+use memmap2::{Mmap, MmapOptions};
+use dashmap::mapref::one::{Ref, RefMut, MappedRef};
 
 pub type FxDashSet<V>    = DashSet<V, FxBuildHasher>;
 pub type FxDashMap<K, V> = DashMap<K, V, FxBuildHasher>;
@@ -19,41 +22,39 @@ type FileRefMut<'a> = RefMut<'a, FileId, StalkrFile>;
 
 pub type FilePathGuard<'a> = MappedRef<'a, FileId, StalkrFile, String>;
 
-type MmapGuardMut<'a> = MappedRefMut<'a, FileId, StalkrFile, MmapMut>;
-
 #[derive(Eq, Hash, Copy, Clone, Debug, PartialEq)]
 pub struct FileId(u32);
 
 #[derive(Debug)]
 pub enum StalkrFileContents {
     Buf(Vec<u8>),
-    Mmap(MmapMut)
+    Mmap(Mmap)
 }
 
 impl StalkrFileContents {
-    #[track_caller]
     #[inline(always)]
     #[must_use]
-    pub fn as_buf_unchecked(&self) -> &Vec<u8> {
+    pub fn as_bytes(&self) -> &[u8] {
         match self {
             Self::Buf(b) => b,
-            Self::Mmap(_) => unsafe { hint::unreachable_unchecked() }
+            Self::Mmap(m) => m
         }
     }
 
     #[track_caller]
     #[inline(always)]
     #[must_use]
-    pub fn as_mmap_unchecked(&self) -> &MmapMut {
+    pub fn as_buf_unchecked(&self) -> &Vec<u8> {
         match self {
-            Self::Mmap(m) => m,
-            Self::Buf(_) => unsafe { hint::unreachable_unchecked() }
+            Self::Buf(b) => b,
+            Self::Mmap(_) => unsafe { hint::unreachable_unchecked() }
         }
     }
 
     #[track_caller]
     #[inline(always)]
-    pub fn as_mmap_unchecked_mut(&mut self) -> &mut MmapMut {
+    #[must_use]
+    pub fn as_mmap_unchecked(&self) -> &Mmap {
         match self {
             Self::Mmap(m) => m,
             Self::Buf(_) => unsafe { hint::unreachable_unchecked() }
@@ -72,6 +73,9 @@ pub struct StalkrFile {
 
     pub tags: Vec<Tag>,
 
+    /// Hash of the contents when they were scanned, see [`Self::record_hash`]
+    pub hash: u64,
+
     contents: Option<StalkrFileContents>
 }
 
@@ -79,7 +83,28 @@ impl StalkrFile {
     #[inline(always)]
     #[must_use]
     pub fn new(upath: String, handle: File, meta: fs::Metadata) -> Self {
-        Self { meta, upath, handle, tags: Vec::new(), contents: None }
+        Self { meta, upath, handle, tags: Vec::new(), hash: 0, contents: None }
+    }
+
+    /// Remembers the scanned contents, to tell if the file was changed before it's edited
+    #[inline]
+    pub fn record_hash(&mut self) {
+        if let Some(contents) = &self.contents {
+            self.hash = hash_contents(contents.as_bytes());
+        }
+    }
+
+    /// Contents of the file as it is now, and whether they differ from the scanned ones,
+    /// e.g. because the file was edited while stalkr was prompting
+    pub fn read_current(&self) -> io::Result<(Vec<u8>, bool)> {
+        let meta = fs::metadata(&self.upath)?;
+        let contents = fs::read(&self.upath)?;
+
+        let is_changed = meta.len() != self.meta.len()
+            || meta.modified().ok() != self.meta.modified().ok()
+            || hash_contents(&contents) != self.hash;
+
+        Ok((contents, is_changed))
     }
 
     #[inline(always)]
@@ -88,9 +113,25 @@ impl StalkrFile {
         unsafe { self.contents.as_ref().unwrap_unchecked() }
     }
 
-    #[inline(always)]
-    pub fn read_contents_unchecked_mut(&mut self) -> &mut StalkrFileContents {
-        unsafe { self.contents.as_mut().unwrap_unchecked() }
+    /// Lines `line - radius..=line + radius` (1-based) of the loaded contents
+    #[must_use]
+    pub fn lines_around(&self, line: u32, radius: u32) -> String {
+        let Some(contents) = &self.contents else { return String::new() };
+
+        let first = line.saturating_sub(radius).max(1) as usize;
+        let count = (line as usize + radius as usize + 1).saturating_sub(first);
+
+        let mut lines = contents.as_bytes()
+            .split(|b| *b == b'\n')
+            .skip(first - 1)
+            .take(count)
+            .map(|l| String::from_utf8_lossy(l.strip_suffix(b"\r").unwrap_or(l)))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // the file's trailing newline
+        lines.truncate(lines.trim_end_matches('\n').len());
+        lines
     }
 
     #[inline]
@@ -114,7 +155,7 @@ impl StalkrFile {
     }
 
     #[inline]
-    pub fn mmap_file(&mut self) -> io::Result<&MmapMut> {
+    pub fn mmap_file(&mut self) -> io::Result<&Mmap> {
         if let Some(StalkrFileContents::Mmap(_)) = &self.contents {
             return Ok(self.read_contents_unchecked().as_mmap_unchecked())
         }
@@ -123,7 +164,7 @@ impl StalkrFile {
             let mut opts = MmapOptions::new();
             opts.len(self.meta.len() as usize);
 
-            let mmap = unsafe { opts.map_mut(&self.handle)? };
+            let mmap = unsafe { opts.map(&self.handle)? };
 
             self.contents = Some(StalkrFileContents::Mmap(mmap));
         }
@@ -140,6 +181,9 @@ pub struct FileManager {
 
     // seen canonicalized filepaths
     seen: FxDashSet<String>,
+
+    // canonicalized filepaths that were searched for TODO's, only kept when closing
+    scanned: FxDashSet<String>,
 }
 
 impl FileManager {
@@ -166,30 +210,56 @@ impl FileManager {
         self.get_file_unchecked_mut(file_id).tags.push(tag);
     }
 
-    pub fn get_mmap_or_remmap_file_mut(
+    /// Current contents of the file, to be edited at `ranges` of its scanned contents.
+    /// If the file was changed since it was scanned, the ranges are looked up again by their
+    /// lines (see [`reanchor`]), and it's an error if any of them can't be found anymore.
+    pub fn read_for_edit(
         &self,
         file_id: FileId,
-        new_len: usize,
-    ) -> io::Result<MmapGuardMut<'_>> {
-        let mut entry = self.get_file_unchecked_mut(file_id);
+        ranges: &mut [Range<usize>]
+    ) -> anyhow::Result<Vec<u8>> {
+        let file = self.get_file_unchecked(file_id);
+        let path = &file.upath;
 
-        let orig_len = entry.meta.len() as usize;
+        let (current, is_changed) = file.read_current()
+            .with_context(|| format!("couldn't read {path}"))?;
 
-        if orig_len == new_len && matches!(entry.contents, Some(StalkrFileContents::Mmap(_))) {
-            // no need to remmap
-            return Ok(entry.map(|f| f.read_contents_unchecked_mut().as_mmap_unchecked_mut()))
-        }
+        if !is_changed { return Ok(current) }
+
+        let scanned = file.read_contents_unchecked().as_bytes();
+
+        for range in ranges.iter_mut() {
+            let line = bytecount::count(&scanned[..range.start], b'\n') + 1;
 
-        entry.handle.set_len(new_len as _)?;
+            *range = match reanchor(scanned, &current, range.clone()) {
+                Ok(anchored) => anchored,
 
-        let mut opts = MmapOptions::new();
-        opts.len(new_len);
+                Err(Unanchored::Gone) => bail!{
+                    "{path} was changed since it was scanned and the TODO on line {line} \
+                     can't be found in it anymore, leaving the file alone"
+                },
 
-        let mmap = unsafe { opts.map_mut(&entry.handle)? };
+                Err(Unanchored::Ambiguous) => bail!{
+                    "{path} was changed since it was scanned and the TODO on line {line} \
+                     is in it more than once now, leaving the file alone"
+                }
+            };
+        }
+
+        // two TODOs found on the same lines, e.g. when one of two equal lines was deleted
+        let mut sorted = ranges.iter().collect::<Vec<_>>();
+        sorted.sort_by_key(|r| r.start);
 
-        entry.contents = Some(StalkrFileContents::Mmap(mmap));
+        if sorted.windows(2).any(|w| w[0].end > w[1].start) {
+            bail!{
+                "{path} was changed since it was scanned and its TODOs can't be told apart anymore, \
+                 leaving the file alone"
+            }
+        }
 
-        Ok(entry.map(|f| f.read_contents_unchecked_mut().as_mmap_unchecked_mut()))
+        eprintln!("[{path} was changed since it was scanned, its TODOs were found again by their lines]");
+
+        Ok(current)
     }
 
     #[inline]
@@ -214,6 +284,21 @@ impl FileManager {
         self.seen.insert(s)
     }
 
+    /// Remembers that `path` was searched, i.e. not skipped as binary or generated
+    #[inline]
+    pub fn mark_scanned(&self, path: &Path) {
+        if let Ok(canonicalized) = fs::canonicalize(path) {
+            self.scanned.insert(canonicalized.to_string_lossy().into_owned());
+        }
+    }
+
+    /// Whether `path` was searched, see [`Self::mark_scanned`]
+    #[inline]
+    #[must_use]
+    pub fn was_scanned(&self, path: &Path) -> bool {
+        fs::canonicalize(path).is_ok_and(|c| self.scanned.contains(&*c.to_string_lossy()))
+    }
+
     #[inline]
     pub fn register_stalkr_file(
         &self,
@@ -223,3 +308,149 @@ impl FileManager {
         self.files.insert(file_id, file);
     }
 }
+
+#[inline]
+fn hash_contents(contents: &[u8]) -> u64 {
+    let mut hasher = FxHasher::default();
+    hasher.write(contents);
+    hasher.finish()
+}
+
+/// Why [`reanchor`] couldn't find a range again
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Unanchored {
+    /// The lines of the range aren't in the current contents anymore
+    Gone,
+    /// They are, but two matches are equally close to where they were
+    Ambiguous
+}
+
+/// Where `range` of the `scanned` contents is in the `current` ones, found by the text
+/// of the lines it is in, which has to be unchanged. The closest match wins.
+pub fn reanchor(scanned: &[u8], current: &[u8], range: Range<usize>) -> Result<Range<usize>, Unanchored> {
+    let line_start = scanned[..range.start]
+        .iter()
+        .rposition(|b| *b == b'\n')
+        .map_or(0, |i| i + 1);
+
+    // up to the end of the last line of the range, the newline only if the range has it
+    let line_end = if range.end > range.start && scanned[range.end - 1] == b'\n' {
+        range.end
+    } else {
+        scanned[range.end..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .map_or(scanned.len(), |i| range.end + i)
+    };
+
+    let lines = &scanned[line_start..line_end];
+
+    // whole lines only, not a line that merely ends the same
+    let is_whole = |start: usize| {
+        let end = start + lines.len();
+        (start == 0 || current[start - 1] == b'\n') &&
+        (lines.ends_with(b"\n") || end == current.len() || current[end] == b'\n')
+    };
+
+    let mut best: Option<(usize, usize)> = None;
+    let mut is_tie = false;
+
+    for start in memchr::memmem::find_iter(current, lines).filter(|s| is_whole(*s)) {
+        let distance = start.abs_diff(line_start);
+
+        match best {
+            Some((_, d)) if distance > d => {}
+            Some((_, d)) if distance == d => is_tie = true,
+            _ => {
+                best = Some((start, distance));
+                is_tie = false;
+            }
+        }
+    }
+
+    if is_tie { return Err(Unanchored::Ambiguous) }
+
+    let (start, _) = best.ok_or(Unanchored::Gone)?;
+
+    Ok(start + range.start - line_start..start + range.end - line_start)
+}
+
+/// Replaces the contents of the file at `path` without ever leaving it half-written:
+/// `contents` go to a temporary file next to it, which is synced and renamed over it.
+/// A symlink stays a symlink, its target is replaced. Permissions and ownership are kept.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let target = fs::canonicalize(path)?;
+    let meta = fs::metadata(&target)?;
+
+    let (Some(dir), Some(name)) = (target.parent(), target.file_name()) else {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))
+    };
+
+    let tmp_path = dir.join(format!{
+        ".{name}.stalkr-{pid}.tmp",
+        name = name.to_string_lossy(),
+        pid = std::process::id()
+    });
+
+    let written = (|| {
+        let mut tmp = OpenOptions::new().write(true).create_new(true).open(&tmp_path)?;
+
+        tmp.write_all(contents)?;
+
+        #[cfg(unix)]
+        keep_owner(&tmp, &meta, path)?;
+
+        // after the chown, as it clears the setuid and setgid bits
+        tmp.set_permissions(meta.permissions())?;
+        tmp.sync_all()?;
+
+        fs::rename(&tmp_path, &target)
+    })();
+
+    if written.is_err() {
+        _ = fs::remove_file(&tmp_path);
+        return written
+    }
+
+    // make the rename itself durable
+    #[cfg(unix)] {
+        File::open(dir)?.sync_all()?;
+    }
+
+    Ok(())
+}
+
+/// Gives `tmp` the owner and group of the file it replaces, as far as we're allowed to:
+/// only root can give a file away, and only to a group it's in otherwise
+#[cfg(unix)]
+fn keep_owner(tmp: &File, meta: &fs::Metadata, path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::{fchown, MetadataExt};
+
+    let is_denied = |e: &io::Error| e.kind() == io::ErrorKind::PermissionDenied;
+
+    let tmp_meta = tmp.metadata()?;
+
+    if tmp_meta.uid() != meta.uid() {
+        match fchown(tmp, Some(meta.uid()), Some(meta.gid())) {
+            Ok(()) => return Ok(()),
+            Err(e) if is_denied(&e) => eprintln!{
+                "[couldn't keep the owner of {path}, it's now owned by you]",
+                path = path.display()
+            },
+            Err(e) => return Err(e)
+        }
+    }
+
+    if tmp_meta.gid() != meta.gid() {
+        match fchown(tmp, None, Some(meta.gid())) {
+            Ok(()) => {}
+            Err(e) if is_denied(&e) => eprintln!{
+                "[couldn't keep the group of {path}, it now has your primary group]",
+                path = path.display()
+            },
+            Err(e) => return Err(e)
+        }
+    }
+
+    Ok(())
+}
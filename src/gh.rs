@@ -1,22 +1,88 @@
-use crate::tag::Tag;
-use crate::api::Api;
+use crate::util;
+use crate::http;
+use crate::api::{Api, PullRequest};
 use crate::todo::Todo;
 use crate::config::Config;
-use crate::issue::{Issue, Issuer};
+use crate::template::IssueText;
+use crate::meta::Milestones;
+use crate::index::OpenIssue;
+use crate::issue::{Issue, Issuer, IssueStates};
 
 use std::env;
-use std::sync::atomic::Ordering;
+use std::fmt::Write;
 
-use surf::StatusCode;
+use anyhow::Context;
 use serde_json::Value;
 
-pub struct GithubApi;
+/// github.com or a GitHub Enterprise Server instance
+pub struct GithubApi {
+    /// e.g. `https://github.com` or `https://git.corp.example`
+    pub web_url: Box<str>,
+
+    /// e.g. `https://api.github.com` or `https://git.corp.example/api/v3`
+    pub api_url: Box<str>,
+
+    /// e.g. `https://api.github.com/graphql` or `https://git.corp.example/api/graphql`
+    pub graphql_url: Box<str>,
+
+    pub token_env_var: Box<str>,
+
+    pub milestones: Milestones
+}
+
+impl GithubApi {
+    pub const DEFAULT_WEB_URL: &str = "https://github.com";
+    pub const DEFAULT_API_URL: &str = "https://api.github.com";
+    pub const DEFAULT_TOKEN_ENV_VAR: &str = "STALKR_GITHUB_TOKEN";
+
+    /// Enterprise Server serves the REST api under `/api/v3` of the web host
+    #[must_use]
+    pub fn new(web_url: &str, api_url: Option<&str>, token_env_var: Option<&str>) -> Self {
+        let web_url = web_url.trim_end_matches('/');
+
+        let api_url = match api_url {
+            Some(api_url) => api_url.trim_end_matches('/').to_owned(),
+            None if web_url == Self::DEFAULT_WEB_URL => Self::DEFAULT_API_URL.to_owned(),
+            None => format!("{web_url}/api/v3")
+        };
+
+        // Enterprise Server serves GraphQL next to the REST api, not under it
+        let graphql_url = match api_url.strip_suffix("/v3") {
+            Some(api) => format!("{api}/graphql"),
+            None => format!("{api_url}/graphql")
+        };
+
+        Self {
+            web_url: web_url.into(),
+            api_url: api_url.into(),
+            graphql_url: graphql_url.into(),
+            token_env_var: token_env_var.unwrap_or(Self::DEFAULT_TOKEN_ENV_VAR).into(),
+            milestones: Milestones::default()
+        }
+    }
+
+    async fn list_milestones(&self, config: &Config) -> anyhow::Result<Vec<(Box<str>, u64)>> {
+        const PER_PAGE: usize = 100;
+
+        let Config { owner, repo, .. } = config;
+        let url = format!("{api}/repos/{owner}/{repo}/milestones", api = self.api_url);
+
+        let milestones = config.http.get_all_pages(PER_PAGE, |client, page| {
+            client.get(format!("{url}?state=all&per_page={PER_PAGE}&page={page}"))
+                .header("Authorization", format!("token {}", config.token()))
+                .header("Accept", "application/vnd.github.v3+json")
+                .header("User-Agent", "stalkr-todo-bot")
+        }).await?;
+
+        Ok(Milestones::from_json(&milestones, "number"))
+    }
+}
 
 #[async_trait::async_trait]
 impl Api for GithubApi {
     #[inline(always)]
-    fn get_api_token_env_var(&self) -> &'static str {
-        "STALKR_GITHUB_TOKEN"
+    fn get_api_token_env_var(&self) -> &str {
+        &self.token_env_var
     }
 
     #[inline(always)]
@@ -28,7 +94,8 @@ impl Api for GithubApi {
     fn get_project_url(&self, config: &Config) -> String {
         let Config { owner, repo, .. } = config;
         format!{
-            "https://github.com/{owner}/{repo}"
+            "{web}/{owner}/{repo}",
+            web = self.web_url
         }
     }
 
@@ -36,7 +103,8 @@ impl Api for GithubApi {
     fn get_issues_api_url(&self, config: &Config) -> String {
         let Config { owner, repo, .. } = config;
         format!{
-            "https://api.github.com/repos/{owner}/{repo}/issues"
+            "{api}/repos/{owner}/{repo}/issues",
+            api = self.api_url
         }
     }
 
@@ -45,116 +113,199 @@ impl Api for GithubApi {
         let Config { owner, repo, .. } = config;
         let issue_number = issue.issue_number;
         format!{
-            "https://api.github.com/repos/{owner}/{repo}/issues/{issue_number}"
+            "{api}/repos/{owner}/{repo}/issues/{issue_number}",
+            api = self.api_url
         }
     }
 
     #[inline]
-    fn make_client(&self, _config: &Config) -> surf::Result<surf::Client> {
+    fn get_permalink(&self, config: &Config, sha: &str, path: &str, line: u32) -> Option<String> {
+        Some(format!{
+            "{project}/blob/{sha}/{path}#L{line}",
+            project = self.get_project_url(config),
+            path = util::percent_encode(path, b"/-._~")
+        })
+    }
+
+    #[inline]
+    fn make_client(&self) -> surf::Result<surf::Client> {
         Ok(surf::Client::new())
     }
 
-    async fn post_issue(&self, issuer: &Issuer, todo: Todo) {
-        let body = todo.as_json_value();
-
-        let rq = match issuer.rq_client
-            .post(&*issuer.issues_api_url)
-            .header("Authorization", format!("token {}", issuer.config.token()))
-            .header("Accept", "application/vnd.github.v3+json")
-            .header("User-Agent", "stalkr-todo-bot")
-            .body_json(&body)
-        {
-            Ok(rq) => rq,
-            Err(e) => {
-                eprintln!("[error creating request: {e}]");
-                return
-            }
-        };
+    async fn post_issue(&self, issuer: &Issuer, todo: &Todo, text: &IssueText) -> anyhow::Result<u64> {
+        let mut body = todo.as_json_value(text, &issuer.config.settings.issues.labels);
 
-        match rq.await {
-            Ok(mut r) if r.status().is_success() => {
-                match r.body_json::<Value>().await {
-                    Ok(json) => {
-                        let issue_number = json
-                            .get("number")
-                            .and_then(serde_json::Value::as_u64)
-                            .ok_or_else(|| anyhow::anyhow!("could not parse issue id"));
-
-                        match issue_number {
-                            Ok(issue_number) => {
-                                let file_id = todo.loc.file_id();
-                                let tag = Tag { issue_number, todo };
-                                issuer.fm.add_tag_to_file(file_id, tag);
-                            }
-                            Err(e) => eprintln!("[failed to parse JSON response: {e}]")
-                        }
-                    }
-                    Err(e) => eprintln!("[failed to parse JSON response: {e}]")
-                }
-            }
+        self.milestones.resolve_in(
+            &mut body,
+            "milestone",
+            self.list_milestones(&issuer.config)
+        ).await;
 
-            Ok(r) if matches!{
-                r.status(),
-                StatusCode::Forbidden | StatusCode::TooManyRequests
-            } => eprintln!{
-                "[presumably rate limit hit: HTTP {status}]",
-                status = r.status()
-            },
-
-            Ok(mut r) => {
-                let text = r.body_string().await.unwrap_or_default();
-                eprintln!{
-                    "[failed to create issue ({s}): {t}]",
-                    s = r.status(),
-                    t = text
-                }
-            },
+        let r = issuer.config.http.send(|client| {
+            client.post(&*issuer.issues_api_url)
+                .header("Authorization", format!("token {}", issuer.config.token()))
+                .header("Accept", "application/vnd.github.v3+json")
+                .header("User-Agent", "stalkr-todo-bot")
+                .body_json(&body)
+        }).await?;
 
-            Err(e) => eprintln!("[network error creating issue: {e}]")
-        }
+        let json = http::into_json(r, "create issue").await?;
+
+        json.get("number").and_then(Value::as_u64).context("could not parse issue id")
     }
 
-    async fn check_if_issue_is_closed(&self, issuer: &Issuer, issue: &Issue) -> bool {
+    async fn check_if_issue_is_closed(&self, issuer: &Issuer, issue: &Issue) -> anyhow::Result<bool> {
         let url = issuer.config.api.get_issue_api_url(&issuer.config, issue);
 
-        let request = issuer.rq_client
-            .get(&url)
-            .header("Authorization", format!("token {}", issuer.config.token()))
-            .header("Accept", "application/vnd.github.v3+json")
-            .header("User-Agent", "stalkr-todo-bot");
+        let r = issuer.config.http.send(|client| {
+            Ok(client.get(&url)
+                .header("Authorization", format!("token {}", issuer.config.token()))
+                .header("Accept", "application/vnd.github.v3+json")
+                .header("User-Agent", "stalkr-todo-bot"))
+        }).await?;
 
-        match request.send().await {
-            Ok(mut r) if r.status().is_success() => {
-                let Ok(json) = r.body_json::<Value>().await else {
-                    return false
-                };
+        let json = http::into_json(r, "get issue").await?;
+
+        let state = json.get("state").and_then(Value::as_str).context("could not parse issue state")?;
+
+        Ok(state == "closed")
+    }
 
-                let Ok(state) = json.get("state").and_then(|s| s.as_str()).ok_or_else(|| {
-                    anyhow::anyhow!("could not parse issue state")
-                }) else {
-                    return false
+    /// One GraphQL query per 100 issues, with an alias per issue
+    async fn get_issue_states(&self, config: &Config, numbers: &[u64]) -> anyhow::Result<IssueStates> {
+        const PER_QUERY: usize = 100;
+
+        let mut states = IssueStates::default();
+
+        for chunk in numbers.chunks(PER_QUERY) {
+            let mut query = String::from(
+                "query($owner: String!, $name: String!) { repository(owner: $owner, name: $name) {"
+            );
+
+            for n in chunk {
+                // tags may refer to pull requests too
+                _ = write!{
+                    query,
+                    " i{n}: issueOrPullRequest(number: {n}) {{ \
+                        ... on Issue {{ state }} ... on PullRequest {{ state }} }}"
                 };
+            }
+
+            query.push_str(" } }");
+
+            let body = serde_json::json!({
+                "query": query,
+                "variables": { "owner": &*config.owner, "name": &*config.repo }
+            });
+
+            let r = config.http.send_query(|client| {
+                client.post(&*self.graphql_url)
+                    .header("Authorization", format!("bearer {}", config.token()))
+                    .header("User-Agent", "stalkr-todo-bot")
+                    .body_json(&body)
+            }).await?;
+
+            let json = http::into_json(r, "get issue states").await?;
+
+            // missing issues come back as `null` with an error, the rest is still there
+            let Some(repository) = json.pointer("/data/repository") else {
+                anyhow::bail!("failed to get issue states: {errors}", errors = json["errors"])
+            };
 
-                if state == "closed" {
-                    issuer.config.found_closed_todo.store(true, Ordering::SeqCst);
-                    true
-                } else {
-                    false
+            for n in chunk {
+                let state = repository
+                    .get(format!("i{n}"))
+                    .and_then(|issue| issue.get("state"))
+                    .and_then(Value::as_str);
+
+                if let Some(state) = state {
+                    // pull requests can be `MERGED` too
+                    states.insert(*n, state != "OPEN");
                 }
             }
+        }
 
-            Ok(r) if matches!{
-                r.status(),
-                StatusCode::Forbidden | StatusCode::TooManyRequests
-            } => {
-                // TODO(#27): A mechanism to stop execution
-                eprintln!{
-                    "[presumably rate limit hit: HTTP {status}]",
-                    status = r.status()
-                }; false
-            }
+        Ok(states)
+    }
 
-            _ => false
-        }
+    async fn close_issue(&self, issuer: &Issuer, issue: &Issue, comment: &str) -> anyhow::Result<()> {
+        let url = issuer.config.api.get_issue_api_url(&issuer.config, issue);
+
+        let comment = serde_json::json!({ "body": comment });
+
+        let r = issuer.config.http.send(|client| {
+            client.post(format!("{url}/comments"))
+                .header("Authorization", format!("token {}", issuer.config.token()))
+                .header("Accept", "application/vnd.github.v3+json")
+                .header("User-Agent", "stalkr-todo-bot")
+                .body_json(&comment)
+        }).await?;
+
+        http::into_json(r, "comment on issue").await?;
+
+        let state = serde_json::json!({ "state": "closed", "state_reason": "completed" });
+
+        let r = issuer.config.http.send(|client| {
+            client.patch(&url)
+                .header("Authorization", format!("token {}", issuer.config.token()))
+                .header("Accept", "application/vnd.github.v3+json")
+                .header("User-Agent", "stalkr-todo-bot")
+                .body_json(&state)
+        }).await?;
+
+        http::into_json(r, "close issue").await?;
+
+        Ok(())
+    }
+
+    async fn open_pull_request(&self, config: &Config, pr: &PullRequest) -> anyhow::Result<Option<String>> {
+        let Config { owner, repo, .. } = config;
+        let url = format!("{api}/repos/{owner}/{repo}/pulls", api = self.api_url);
+
+        let body = serde_json::json!({
+            "title": pr.title,
+            "body": pr.body,
+            "head": pr.head,
+            "base": pr.base
+        });
+
+        let r = config.http.send(|client| {
+            client.post(&url)
+                .header("Authorization", format!("token {}", config.token()))
+                .header("Accept", "application/vnd.github.v3+json")
+                .header("User-Agent", "stalkr-todo-bot")
+                .body_json(&body)
+        }).await?;
+
+        let json = http::into_json(r, "open pull request").await?;
+
+        json.get("html_url")
+            .and_then(Value::as_str)
+            .map(|url| Some(url.to_owned()))
+            .context("could not parse pull request url")
+    }
+
+    async fn list_open_issues(&self, config: &Config) -> anyhow::Result<Vec<OpenIssue>> {
+        const PER_PAGE: usize = 100;
+
+        let url = self.get_issues_api_url(config);
+
+        let issues = config.http.get_all_pages(PER_PAGE, |client, page| {
+            client.get(format!("{url}?state=open&per_page={PER_PAGE}&page={page}"))
+                .header("Authorization", format!("token {}", config.token()))
+                .header("Accept", "application/vnd.github.v3+json")
+                .header("User-Agent", "stalkr-todo-bot")
+        }).await?;
+
+        Ok(issues.iter()
+            // the issues endpoint also returns pull requests
+            .filter(|issue| issue.get("pull_request").is_none())
+            .filter_map(|issue| Some(OpenIssue::new(
+                issue.get("number")?.as_u64()?,
+                issue.get("title")?.as_str()?,
+                issue.get("body").and_then(Value::as_str),
+                issue.get("labels")
+            )))
+            .collect())
     }
 }
@@ -1,43 +1,708 @@
-use std::sync::Mutex;
-use std::process::Command;
+use crate::fm;
+use crate::hunk::{self, Base, Hunk, LineMap, LineMaps};
 
-use anyhow::bail;
+use std::fs;
+use std::io::Write;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
 
+use anyhow::{bail, Context};
+use rustc_hash::FxBuildHasher;
+
+/// Where the repository of the scanned directory lives
+#[derive(Debug, Clone)]
+pub struct Repo {
+    /// Top level directory of the checkout
+    pub work_tree: PathBuf,
+
+    /// `.git` itself, or where a `.git` file points to with `gitdir: <path>`,
+    /// e.g. `.git/worktrees/<name>` of a linked worktree or `.git/modules/<name>` of a submodule
+    pub git_dir: PathBuf,
+
+    /// Where the config and the refs are, shared by all worktrees of a repository
+    pub common_dir: PathBuf
+}
+
+impl Repo {
+    /// Looks for `.git` in `dir` and its parents
+    #[must_use]
+    pub fn discover(dir: &Path) -> Option<Self> {
+        let mut dir = fs::canonicalize(dir).ok()?;
+
+        loop {
+            let dot_git = dir.join(".git");
+
+            if dot_git.is_dir() {
+                return Some(Self::new(dir, dot_git))
+            }
+
+            if dot_git.is_file() {
+                let contents = fs::read_to_string(&dot_git).ok()?;
+                let git_dir = contents.lines().find_map(|l| l.strip_prefix("gitdir:"))?.trim();
+
+                // relative to the directory of the `.git` file
+                let git_dir = dir.join(git_dir);
+                let git_dir = fs::canonicalize(&git_dir).unwrap_or(git_dir);
+
+                return Some(Self::new(dir, git_dir))
+            }
+
+            if !dir.pop() { return None }
+        }
+    }
+
+    fn new(work_tree: PathBuf, git_dir: PathBuf) -> Self {
+        // linked worktrees point to their main repository in `commondir`
+        let common_dir = fs::read_to_string(git_dir.join("commondir"))
+            .ok()
+            .map(|common_dir| git_dir.join(common_dir.trim()))
+            .map_or_else(|| git_dir.clone(), |common_dir| {
+                fs::canonicalize(&common_dir).unwrap_or(common_dir)
+            });
+
+        Self { work_tree, git_dir, common_dir }
+    }
+
+    /// `path` (relative to the process' working directory) relative to the work tree,
+    /// as git expects it with `-C <work tree>`
+    #[must_use]
+    pub fn relative_path(&self, path: &str) -> String {
+        let path = Path::new(path);
+
+        // only the parent, so that a symlink is still the symlink and not where it points to
+        let absolute = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => fs::canonicalize(
+                if parent.as_os_str().is_empty() { Path::new(".") } else { parent }
+            ).map(|parent| parent.join(name)),
+            _ => fs::canonicalize(path)
+        };
+
+        let absolute = absolute.unwrap_or_else(|_| path.to_owned());
+
+        let relative = absolute.strip_prefix(&self.work_tree).unwrap_or(&absolute);
+
+        relative.to_string_lossy().replace('\\', "/")
+    }
+}
+
+/// When the edits of a run get committed, the `commit` key of `.stalkr.json`
+#[derive(Eq, Copy, Clone, Debug, Default, PartialEq)]
+pub enum CommitStrategy {
+    /// One commit per inserted or removed tag
+    #[default]
+    PerTag,
+    /// One commit per edited file, listing all of its tags
+    PerFile,
+    /// One commit at the end of the run, listing all tags.
+    /// If any file failed, the edits of the others are undone and nothing is committed.
+    PerRun,
+    /// The edits are left uncommitted
+    None
+}
+
+impl CommitStrategy {
+    pub const NAMES: &str = "per-tag, per-file, per-run, none";
+
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "per-tag"  => Some(Self::PerTag),
+            "per-file" => Some(Self::PerFile),
+            "per-run"  => Some(Self::PerRun),
+            "none"     => Some(Self::None),
+            _ => None
+        }
+    }
+}
+
+/// What happens to files with uncommitted changes, which would end up in stalkr's commits
+#[derive(Eq, Copy, Clone, Debug, Default, PartialEq)]
+pub enum DirtyFiles {
+    /// They are left alone, with a warning
+    #[default]
+    Skip,
+    /// They are edited and committed as a whole, see `--allow-dirty`
+    Allow,
+    /// They are edited, but only stalkr's lines are committed, see `--stage-own-changes`
+    StageOwnChanges
+}
+
+/// A file edited by stalkr that is waiting for the per-run commit
+struct Pending {
+    path: String,
+
+    /// How the file is referred to in the commit message
+    name: String,
+
+    msgs: Vec<String>,
+    hunks: Vec<Hunk>,
+
+    /// Contents before stalkr edited it, to undo the edit if the run fails
+    original: Vec<u8>
+}
+
+/// Serializes git commands and groups commits according to the [`CommitStrategy`]
 pub struct GitLocker {
     mutex: Mutex<()>,
+    strategy: CommitStrategy,
+    dirty_files: DirtyFiles,
+
+    /// Repository of the scanned directory, git runs in the process' directory without one
+    repo: Option<Repo>,
+
+    /// Paths (relative to the work tree) with uncommitted changes before anything was edited
+    dirty: OnceLock<HashSet<String, FxBuildHasher>>,
+
+    pending: Mutex<Vec<Pending>>,
+
+    /// Set if editing some file failed, so the per-run commit would be incomplete
+    failed: AtomicBool,
+
+    /// Branch to make the commits on, see [`Self::commit_onto_branch`]
+    branch: OnceLock<Box<str>>,
+
+    /// Set once `branch` was created and checked out, before the first commit
+    is_on_branch: AtomicBool
 }
 
 impl Default for GitLocker {
     fn default() -> Self {
-        Self::new()
+        Self::new(CommitStrategy::default(), DirtyFiles::default(), None)
     }
 }
 
 impl GitLocker {
     #[inline(always)]
     #[must_use] 
-    pub const fn new() -> Self {
-        Self { mutex: Mutex::new(()) }
+    pub const fn new(strategy: CommitStrategy, dirty_files: DirtyFiles, repo: Option<Repo>) -> Self {
+        Self {
+            mutex: Mutex::new(()),
+            strategy,
+            dirty_files,
+            repo,
+            dirty: OnceLock::new(),
+            pending: Mutex::new(Vec::new()),
+            failed: AtomicBool::new(false),
+            branch: OnceLock::new(),
+            is_on_branch: AtomicBool::new(false)
+        }
     }
 
-    pub fn commit_changes(&self, path: &str, msg: &str) -> anyhow::Result<()> {
+    /// Remembers which files have uncommitted changes, has to be called before editing any.
+    /// Nothing to remember if the edits aren't committed or dirty files are committed anyway.
+    pub fn read_status(&self) {
+        if self.strategy == CommitStrategy::None || self.dirty_files == DirtyFiles::Allow {
+            return
+        }
+
+        let output = self.git()
+            .args(["status", "--porcelain", "-z", "--untracked-files=all"])
+            .output();
+
+        let Ok(output) = output.as_ref().map(|o| String::from_utf8_lossy(&o.stdout)) else {
+            eprintln!("[couldn't run git status, no file counts as dirty]");
+            return
+        };
+
+        let mut dirty = HashSet::default();
+        let mut entries = output.split('\0').filter(|e| e.len() > 3);
+
+        // `XY path`, renames and copies are followed by the original path
+        while let Some(entry) = entries.next() {
+            let (status, path) = entry.split_at(3);
+
+            if status.starts_with(['R', 'C']) {
+                entries.next();
+            }
+
+            dirty.insert(path.to_owned());
+        }
+
+        _ = self.dirty.set(dirty);
+    }
+
+    /// Whether `path` had uncommitted changes, see [`Self::read_status`]
+    #[must_use]
+    pub fn is_dirty(&self, path: &str) -> bool {
+        self.dirty.get().is_some_and(|dirty| dirty.contains(&self.relative_path(path)))
+    }
+
+    /// Files that were dirty are only left alone with [`DirtyFiles::Skip`]
+    #[inline]
+    #[must_use]
+    pub fn should_skip(&self, path: &str) -> bool {
+        self.dirty_files == DirtyFiles::Skip && self.is_dirty(path)
+    }
+
+    /// Whether the changes of `path` have to be passed as [`Hunk`]s to commit them
+    #[inline]
+    #[must_use]
+    pub fn needs_hunks(&self, path: &str) -> bool {
+        self.dirty_files == DirtyFiles::StageOwnChanges && self.is_dirty(path)
+    }
+
+    /// Whether every tag gets its own commit, so the file has to be written after each of them
+    #[inline(always)]
+    #[must_use]
+    pub fn commits_every_tag(&self) -> bool {
+        self.strategy == CommitStrategy::PerTag
+    }
+
+    /// Called after every tag written to `path`, `hunks` being its changes if [`Self::needs_hunks`]
+    #[inline]
+    pub fn commit_tag(&self, path: &str, msg: &str, hunks: &[Hunk]) -> anyhow::Result<()> {
+        if self.strategy == CommitStrategy::PerTag {
+            self.commit_paths(&[(path, hunks)], msg)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether [`Self::commit_file`] needs the contents of the file before it was edited
+    #[inline(always)]
+    #[must_use]
+    pub fn keeps_originals(&self) -> bool {
+        self.strategy == CommitStrategy::PerRun
+    }
+
+    /// Called once all tags of `path` are written, `msgs` being their messages.
+    /// `name` is how the file is referred to in the per-run commit,
+    /// `original` are the contents before the edit if [`Self::keeps_originals`].
+    pub fn commit_file(
+        &self,
+        path: &str,
+        name: &str,
+        subject: &str,
+        msgs: Vec<String>,
+        hunks: Vec<Hunk>,
+        original: Option<Vec<u8>>
+    ) -> anyhow::Result<()> {
+        match self.strategy {
+            CommitStrategy::PerFile => {
+                let msg = Self::grouped_msg(subject, msgs.iter().map(String::as_str));
+                self.commit_paths(&[(path, &hunks)], &msg)
+            }
+
+            CommitStrategy::PerRun => {
+                self.pending.lock().unwrap().push(Pending {
+                    path: path.to_owned(),
+                    name: name.to_owned(),
+                    msgs,
+                    hunks,
+                    original: original.unwrap_or_default()
+                });
+                Ok(())
+            }
+
+            CommitStrategy::PerTag | CommitStrategy::None => Ok(())
+        }
+    }
+
+    #[inline(always)]
+    pub fn mark_failed(&self) {
+        self.failed.store(true, Ordering::SeqCst);
+    }
+
+    /// Makes the per-run commit, if there's anything to commit. `subject` gets the number of tags.
+    /// If editing some file failed, the files that were edited are put back as they were instead.
+    pub fn commit_run(&self, subject: impl FnOnce(usize) -> String) -> anyhow::Result<()> {
+        if self.strategy != CommitStrategy::PerRun { return Ok(()) }
+
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+
+        if pending.is_empty() { return Ok(()) }
+
+        if self.failed.load(Ordering::SeqCst) {
+            let not_restored = Self::restore(&pending);
+
+            if not_restored > 0 {
+                bail!("not all files could be edited, and {not_restored} of the edited ones couldn't be put back")
+            }
+
+            bail!("not all files could be edited, so the edits of the others were undone")
+        }
+
+        let changes = pending.iter()
+            .map(|p| (p.path.as_str(), p.hunks.as_slice()))
+            .collect::<Vec<_>>();
+
+        let lines = pending.iter().flat_map(|Pending { name, msgs, .. }| {
+            msgs.iter().map(move |msg| format!("{name}: {msg}"))
+        }).collect::<Vec<_>>();
+
+        // a single tag keeps its own message, without the file in front
+        let msg = match &pending[..] {
+            [Pending { msgs, .. }] if msgs.len() == 1 => msgs[0].clone(),
+            _ => Self::grouped_msg(&subject(lines.len()), lines.iter().map(String::as_str))
+        };
+
+        // e.g. a hook rejected it
+        if let Err(e) = self.commit_paths(&changes, &msg) {
+            let not_restored = Self::restore(&pending);
+
+            if not_restored > 0 {
+                bail!("{e:#}, and {not_restored} of the edited files couldn't be put back")
+            }
+
+            bail!("{e:#}, so the edits were undone")
+        }
+
+        Ok(())
+    }
+
+    /// Puts back the files waiting for the per-run commit as they were, e.g. when the run
+    /// is interrupted. Does nothing with other strategies, their edits are already committed.
+    pub fn undo_pending(&self) {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+
+        if pending.is_empty() { return }
+
+        let not_restored = Self::restore(&pending);
+
+        eprintln!{
+            "[the edits of {n} file(s) waiting for the commit were undone]",
+            n = pending.len() - not_restored
+        }
+    }
+
+    // returns how many files couldn't be put back
+    fn restore(pending: &[Pending]) -> usize {
+        pending.iter().filter(|p| {
+            let restored = fm::write_atomically(Path::new(&p.path), &p.original);
+            if let Err(e) = &restored {
+                eprintln!("[couldn't undo the changes of {name}: {e}]", name = p.name);
+            }
+            restored.is_err()
+        }).count()
+    }
+
+    // a single tag keeps its own message
+    fn grouped_msg<'a>(subject: &str, mut msgs: impl ExactSizeIterator<Item = &'a str>) -> String {
+        if msgs.len() == 1 {
+            return msgs.next().unwrap_or_default().to_owned()
+        }
+
+        let mut msg = format!("{subject}\n\n");
+        for line in msgs {
+            msg.push_str("- ");
+            msg.push_str(line);
+            msg.push('\n');
+        }
+
+        msg
+    }
+
+    /// Makes the commits on a new branch `name` off the current commit. It's only created
+    /// (and checked out) right before the first commit, so that a run that commits nothing
+    /// doesn't leave the user on an empty branch. Fails if `name` can't be created.
+    pub fn commit_onto_branch(&self, name: &str) -> anyhow::Result<()> {
+        let is_valid = self.git()
+            .args(["check-ref-format", "--branch", name])
+            .stdout(Stdio::null())
+            .status()?
+            .success();
+
+        if !is_valid {
+            bail!("{name} isn't a valid branch name")
+        }
+
+        let exists = self.git()
+            .args(["rev-parse", "--verify", "--quiet", &format!("refs/heads/{name}")])
+            .stdout(Stdio::null())
+            .status()?
+            .success();
+
+        if exists {
+            bail!("branch {name} already exists")
+        }
+
+        _ = self.branch.set(name.into());
+
+        Ok(())
+    }
+
+    /// Whether the branch of [`Self::commit_onto_branch`] was created, i.e. something was committed
+    #[inline(always)]
+    #[must_use]
+    pub fn is_on_branch(&self) -> bool {
+        self.is_on_branch.load(Ordering::SeqCst)
+    }
+
+    // called with the lock held, right before committing
+    fn switch_to_branch(&self) -> anyhow::Result<()> {
+        let Some(name) = self.branch.get() else { return Ok(()) };
+
+        if self.is_on_branch() { return Ok(()) }
+
+        // the edits and whatever is staged come along, the branch starts at the same commit
+        let status = self.git().args(["checkout", "-q", "-b", name]).status()?;
+
+        if !status.success() {
+            bail!("git checkout -b {name} failed")
+        }
+
+        self.is_on_branch.store(true, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    /// Pushes `branch` to `remote`, setting it as the upstream
+    pub fn push(&self, remote: &str, branch: &str) -> anyhow::Result<()> {
+        let _g = self.mutex.lock().unwrap();
+
+        let status = self.git().args(["push", "-q", "-u", remote, branch]).status()?;
+
+        if !status.success() {
+            bail!("git push {remote} {branch} failed")
+        }
+
+        Ok(())
+    }
+
+    /// `git` running in the work tree
+    fn git(&self) -> Command {
+        let mut git = Command::new("git");
+        if let Some(repo) = &self.repo {
+            git.arg("-C").arg(&repo.work_tree);
+        }
+        git
+    }
+
+    fn relative_path(&self, path: &str) -> String {
+        match &self.repo {
+            Some(repo) => repo.relative_path(path),
+            None => path.to_owned()
+        }
+    }
+
+    // only `changes` end up in the commit, and either all of them or none of them.
+    // Dirty files with `DirtyFiles::StageOwnChanges` only get their hunks committed.
+    fn commit_paths(&self, changes: &[(&str, &[Hunk])], msg: &str) -> anyhow::Result<()> {
         let _g = self.mutex.lock().unwrap();
-        let status = Command::new("git").arg("add").arg(path).status()?;
+
+        self.switch_to_branch()?;
+
+        // for the index and for HEAD, the lines may be elsewhere in them
+        let mut patch = Vec::new();
+        let mut head_patch = Vec::new();
+
+        let mut paths = Vec::with_capacity(changes.len());
+
+        for (path, hunks) in changes {
+            let path = self.relative_path(path);
+
+            if self.dirty_files == DirtyFiles::StageOwnChanges && !hunks.is_empty() {
+                hunk::write_patch(&mut patch, &path, hunks, Base::Staged);
+                hunk::write_patch(&mut head_patch, &path, hunks, Base::Committed);
+            } else {
+                paths.push(path);
+            }
+        }
+
+        if patch.is_empty() {
+            self.commit_whole_files(&paths, msg)
+        } else {
+            self.commit_patch(&patch, &head_patch, &paths, msg)
+        }
+    }
+
+    /// Where the lines of `path` in the work tree are in the index and in HEAD,
+    /// to make [`Hunk`]s of it. Has to be called before `path` is edited.
+    pub fn line_maps(&self, path: &str) -> anyhow::Result<LineMaps> {
+        let path = self.relative_path(path);
+
+        let diff = |rev: Option<&str>| -> anyhow::Result<LineMap> {
+            let output = self.git()
+                .args(["diff", "--no-color", "--no-ext-diff", "-U0"])
+                .args(rev)
+                .arg("--")
+                .arg(&path)
+                .output()?;
+
+            if !output.status.success() {
+                bail!("git diff of {path} failed")
+            }
+
+            Ok(LineMap::from_diff(&String::from_utf8_lossy(&output.stdout)))
+        };
+
+        Ok(LineMaps { staged: diff(None)?, committed: diff(Some("HEAD"))? })
+    }
+
+    fn commit_whole_files(&self, paths: &[String], msg: &str) -> anyhow::Result<()> {
+        let unstage = || {
+            _ = self.git().args(["reset", "-q", "--"]).args(paths).status();
+        };
+
+        let status = self.git().arg("add").arg("--").args(paths).status()?;
 
         if !status.success() {
+            unstage();
             bail!("git add failed")
         }
 
-        let status = Command::new("git")
+        // with the paths, whatever else is staged stays out of the commit
+        let status = self.git()
             .arg("commit")
             .arg("-m")
             .arg(msg)
+            .arg("--")
+            .args(paths)
             .status()?;
 
         if !status.success() {
+            unstage();
             bail!("git commit failed")
         }
 
         Ok(())
     }
+
+    // the commit is made from a separate index of HEAD and stalkr's changes,
+    // so that none of the other uncommitted changes get into it
+    fn commit_patch(&self, patch: &[u8], head_patch: &[u8], paths: &[String], msg: &str) -> anyhow::Result<()> {
+        let Some(repo) = &self.repo else {
+            bail!("no git repository to stage the changes in")
+        };
+
+        // first the real index, so that nothing is touched if the patch doesn't apply
+        if !apply_cached(self.git(), patch, false)? {
+            bail!("couldn't stage only stalkr's changes, the staged version of the file differs too much")
+        }
+
+        let index = repo.git_dir.join("stalkr-index");
+
+        let git = || {
+            let mut git = self.git();
+            git.env("GIT_INDEX_FILE", &index);
+            git
+        };
+
+        let committed = (|| -> anyhow::Result<()> {
+            if !self.git().arg("add").arg("--").args(paths).status()?.success() {
+                bail!("git add failed")
+            }
+
+            if !git().args(["read-tree", "HEAD"]).status()?.success() {
+                bail!("git read-tree HEAD failed")
+            }
+
+            if !apply_cached(git(), head_patch, false)? {
+                bail!("couldn't apply stalkr's changes to HEAD")
+            }
+
+            if !git().arg("add").arg("--").args(paths).status()?.success() {
+                bail!("git add failed")
+            }
+
+            if !git().arg("commit").arg("-m").arg(msg).status()?.success() {
+                bail!("git commit failed")
+            }
+
+            Ok(())
+        })();
+
+        _ = fs::remove_file(&index);
+
+        if committed.is_err() {
+            _ = apply_cached(self.git(), patch, true);
+            _ = self.git().args(["reset", "-q", "--"]).args(paths).status();
+        }
+
+        committed
+    }
+}
+
+// `git apply --cached` of `patch`, without context lines
+fn apply_cached(mut git: Command, patch: &[u8], reverse: bool) -> anyhow::Result<bool> {
+    git.args(["apply", "--cached", "--unidiff-zero"]);
+
+    if reverse {
+        git.arg("--reverse");
+    }
+
+    let mut child = git.arg("-").stdin(Stdio::piped()).spawn()?;
+
+    child.stdin.take().context("no stdin for git apply")?.write_all(patch)?;
+
+    Ok(child.wait()?.success())
+}
+
+/// Commit and branch the scanned tree is at, `None` if unknown (e.g. no commits yet)
+#[derive(Debug, Default)]
+pub struct GitHead {
+    pub sha: Option<Box<str>>,
+
+    /// `None` on a detached HEAD too
+    pub branch: Option<Box<str>>
+}
+
+impl GitHead {
+    #[must_use]
+    pub fn read(dir: &Path) -> Self {
+        let sha = git_output(dir, &["rev-parse", "--verify", "-q", "HEAD"]);
+        let branch = git_output(dir, &["symbolic-ref", "-q", "--short", "HEAD"]);
+        Self { sha, branch }
+    }
+}
+
+/// Author of the last commit that touched `line` (1-based) of `path`
+#[must_use]
+pub fn blame_author(dir: &Path, path: &str, line: u32) -> Option<Box<str>> {
+    let range = format!("{line},{line}");
+    let blame = git_output(dir, &["blame", "--porcelain", "-L", &range, "--", path])?;
+
+    let author = blame.lines().find_map(|l| l.strip_prefix("author "))?;
+
+    // uncommitted lines are blamed on "Not Committed Yet"
+    if blame.lines().any(|l| l == "author-mail <not.committed.yet>") {
+        return None
+    }
+
+    Some(author.into())
+}
+
+/// A commit, as shown to the user
+#[derive(Debug)]
+pub struct Commit {
+    pub sha: Box<str>,
+    pub subject: Box<str>
+}
+
+/// The last commit that changed how many times `needle` occurs, i.e. the one that removed
+/// it if it's gone by now. Looked up in `path` first, as it's much cheaper.
+#[must_use]
+pub fn find_removing_commit(dir: &Path, needle: &str, path: Option<&str>) -> Option<Commit> {
+    let pickaxe = format!("-S{needle}");
+
+    let log = |path: Option<&str>| {
+        let mut args = vec!["log", "-1", "--format=%h%x00%s", pickaxe.as_str()];
+        if let Some(path) = path {
+            args.extend(["--", path]);
+        }
+        git_output(dir, &args)
+    };
+
+    let log = path.and_then(|path| log(Some(path))).or_else(|| log(None))?;
+
+    let (sha, subject) = log.split_once('\0')?;
+
+    Some(Commit { sha: sha.into(), subject: subject.into() })
+}
+
+// trimmed stdout of a successful git command
+fn git_output(dir: &Path, args: &[&str]) -> Option<Box<str>> {
+    let output = Command::new("git").arg("-C").arg(dir).args(args).output().ok()?;
+
+    if !output.status.success() {
+        return None
+    }
+
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    let stdout = stdout.trim();
+
+    (!stdout.is_empty()).then(|| stdout.into())
 }
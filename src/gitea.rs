@@ -0,0 +1,357 @@
+use crate::util;
+use crate::http;
+use crate::api::{Api, PullRequest};
+use crate::todo::Todo;
+use crate::config::Config;
+use crate::template::IssueText;
+use crate::meta::Milestones;
+use crate::index::OpenIssue;
+use crate::issue::{Issue, Issuer, IssueStates};
+
+use std::env;
+
+use anyhow::Context;
+use serde_json::Value;
+use tokio::sync::Mutex;
+use futures::{stream, StreamExt};
+
+/// `(name, id)` of labels
+type Labels = Vec<(Box<str>, u64)>;
+
+/// Gitea and Forgejo (they share the same api), through `/api/v1`.
+pub struct GiteaApi {
+    /// e.g. `https://codeberg.org`
+    pub base_url: Box<str>,
+
+    pub token_env_var: Box<str>,
+
+    pub milestones: Milestones,
+
+    /// Labels of the repository, fetched once per run and extended with the ones we create.
+    /// Locked while resolving, to not create a label twice.
+    labels: Mutex<Option<Labels>>
+}
+
+impl GiteaApi {
+    pub const DEFAULT_TOKEN_ENV_VAR: &str = "STALKR_GITEA_TOKEN";
+
+    // color of the labels we create, the same one GitHub gives new labels
+    const NEW_LABEL_COLOR: &str = "#ededed";
+
+    #[inline]
+    #[must_use]
+    pub fn new(base_url: &str, token_env_var: Option<&str>) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').into(),
+            token_env_var: token_env_var.unwrap_or(Self::DEFAULT_TOKEN_ENV_VAR).into(),
+            milestones: Milestones::default(),
+            labels: Mutex::new(None)
+        }
+    }
+
+    #[inline]
+    fn get_labels_api_url(&self, config: &Config) -> String {
+        let Config { owner, repo, .. } = config;
+        format!("{base}/api/v1/repos/{owner}/{repo}/labels", base = self.base_url)
+    }
+
+    async fn list_labels(&self, config: &Config) -> anyhow::Result<Labels> {
+        const PER_PAGE: usize = 50;
+
+        let url = self.get_labels_api_url(config);
+
+        let labels = config.http.get_all_pages(PER_PAGE, |client, page| {
+            client.get(format!("{url}?limit={PER_PAGE}&page={page}"))
+                .header("Authorization", format!("token {}", config.token()))
+                .header("Accept", "application/json")
+                .header("User-Agent", "stalkr-todo-bot")
+        }).await?;
+
+        Ok(labels.iter().filter_map(|l| Some((
+            l.get("name")?.as_str()?.into(),
+            l.get("id")?.as_u64()?
+        ))).collect())
+    }
+
+    async fn create_label(&self, config: &Config, name: &str) -> anyhow::Result<u64> {
+        let url = self.get_labels_api_url(config);
+
+        let body = serde_json::json!({ "name": name, "color": Self::NEW_LABEL_COLOR });
+
+        let r = config.http.send(|client| {
+            client.post(&url)
+                .header("Authorization", format!("token {}", config.token()))
+                .header("Accept", "application/json")
+                .header("User-Agent", "stalkr-todo-bot")
+                .body_json(&body)
+        }).await?;
+
+        let json = http::into_json(r, "create label").await?;
+
+        json.get("id").and_then(Value::as_u64).context("could not parse label id")
+    }
+
+    /// Gitea only takes label ids, so the label names of an issue payload are replaced
+    /// with their ids, creating the labels that don't exist yet
+    async fn resolve_labels_in(&self, config: &Config, body: &mut Value) {
+        let Some(obj) = body.as_object_mut() else { return };
+        let Some(Value::Array(names)) = obj.remove("labels") else { return };
+
+        let names = names.iter().filter_map(Value::as_str).collect::<Vec<_>>();
+
+        let mut labels = self.labels.lock().await;
+
+        if labels.is_none() {
+            match self.list_labels(config).await {
+                Ok(fetched) => *labels = Some(fetched),
+                Err(e) => {
+                    eprintln!("[couldn't fetch labels, leaving out {names:?}: {e:#}]");
+                    return
+                }
+            }
+        }
+
+        let Some(labels) = labels.as_mut() else { return };
+
+        let mut ids = Vec::with_capacity(names.len());
+
+        for name in names {
+            if let Some((_, id)) = labels.iter().find(|(n, _)| &**n == name) {
+                ids.push(*id);
+                continue
+            }
+
+            match self.create_label(config, name).await {
+                Ok(id) => {
+                    labels.push((name.into(), id));
+                    ids.push(id);
+                }
+
+                Err(e) => eprintln!("[couldn't create label {name:?}, leaving it out: {e:#}]")
+            }
+        }
+
+        if !ids.is_empty() {
+            obj.insert("labels".to_owned(), ids.into());
+        }
+    }
+
+    /// Returns: (number, whether it's closed), `None` if it couldn't be fetched
+    async fn get_issue_state(&self, config: &Config, n: u64) -> Option<(u64, bool)> {
+        let url = format!("{issues}/{n}", issues = self.get_issues_api_url(config));
+
+        let r = config.http.send(|client| {
+            Ok(client.get(&url)
+                .header("Authorization", format!("token {}", config.token()))
+                .header("Accept", "application/json")
+                .header("User-Agent", "stalkr-todo-bot"))
+        }).await.ok()?;
+
+        // pull requests are issues too, with the same states
+        let json = http::into_json(r, "get issue").await.ok()?;
+
+        Some((n, json.get("state")?.as_str()? == "closed"))
+    }
+
+    async fn list_milestones(&self, config: &Config) -> anyhow::Result<Vec<(Box<str>, u64)>> {
+        const PER_PAGE: usize = 50;
+
+        let Config { owner, repo, .. } = config;
+        let url = format!("{base}/api/v1/repos/{owner}/{repo}/milestones", base = self.base_url);
+
+        let milestones = config.http.get_all_pages(PER_PAGE, |client, page| {
+            client.get(format!("{url}?state=all&limit={PER_PAGE}&page={page}"))
+                .header("Authorization", format!("token {}", config.token()))
+                .header("Accept", "application/json")
+                .header("User-Agent", "stalkr-todo-bot")
+        }).await?;
+
+        Ok(Milestones::from_json(&milestones, "id"))
+    }
+}
+
+#[async_trait::async_trait]
+impl Api for GiteaApi {
+    #[inline(always)]
+    fn get_api_token_env_var(&self) -> &str {
+        &self.token_env_var
+    }
+
+    #[inline(always)]
+    fn get_api_token(&self) -> anyhow::Result<String> {
+        env::var(self.get_api_token_env_var()).map_err(Into::into)
+    }
+
+    #[inline(always)]
+    fn get_project_url(&self, config: &Config) -> String {
+        let Config { owner, repo, .. } = config;
+        format!{
+            "{base}/{owner}/{repo}",
+            base = self.base_url
+        }
+    }
+
+    #[inline(always)]
+    fn get_issues_api_url(&self, config: &Config) -> String {
+        let Config { owner, repo, .. } = config;
+        format!{
+            "{base}/api/v1/repos/{owner}/{repo}/issues",
+            base = self.base_url
+        }
+    }
+
+    #[inline(always)]
+    fn get_issue_api_url(&self, config: &Config, issue: &Issue) -> String {
+        let Config { owner, repo, .. } = config;
+        let issue_number = issue.issue_number;
+        format!{
+            "{base}/api/v1/repos/{owner}/{repo}/issues/{issue_number}",
+            base = self.base_url
+        }
+    }
+
+    #[inline]
+    fn get_permalink(&self, config: &Config, sha: &str, path: &str, line: u32) -> Option<String> {
+        Some(format!{
+            "{project}/src/commit/{sha}/{path}#L{line}",
+            project = self.get_project_url(config),
+            path = util::percent_encode(path, b"/-._~")
+        })
+    }
+
+    #[inline]
+    fn make_client(&self) -> surf::Result<surf::Client> {
+        Ok(surf::Client::new())
+    }
+
+    async fn post_issue(&self, issuer: &Issuer, todo: &Todo, text: &IssueText) -> anyhow::Result<u64> {
+        let mut body = todo.as_json_value(text, &issuer.config.settings.issues.labels);
+
+        self.resolve_labels_in(&issuer.config, &mut body).await;
+
+        self.milestones.resolve_in(
+            &mut body,
+            "milestone",
+            self.list_milestones(&issuer.config)
+        ).await;
+
+        let r = issuer.config.http.send(|client| {
+            client.post(&*issuer.issues_api_url)
+                .header("Authorization", format!("token {}", issuer.config.token()))
+                .header("Accept", "application/json")
+                .header("User-Agent", "stalkr-todo-bot")
+                .body_json(&body)
+        }).await?;
+
+        let json = http::into_json(r, "create issue").await?;
+
+        json.get("number").and_then(Value::as_u64).context("could not parse issue id")
+    }
+
+    async fn check_if_issue_is_closed(&self, issuer: &Issuer, issue: &Issue) -> anyhow::Result<bool> {
+        let url = issuer.config.api.get_issue_api_url(&issuer.config, issue);
+
+        let r = issuer.config.http.send(|client| {
+            Ok(client.get(&url)
+                .header("Authorization", format!("token {}", issuer.config.token()))
+                .header("Accept", "application/json")
+                .header("User-Agent", "stalkr-todo-bot"))
+        }).await?;
+
+        let json = http::into_json(r, "get issue").await?;
+
+        let state = json.get("state").and_then(Value::as_str).context("could not parse issue state")?;
+
+        Ok(state == "closed")
+    }
+
+    /// Gitea can't list issues by number, so they're fetched one by one, a few at a time.
+    /// Issues that couldn't be fetched are left out, to be checked again before purging.
+    async fn get_issue_states(&self, config: &Config, numbers: &[u64]) -> anyhow::Result<IssueStates> {
+        let states = stream::iter(numbers.iter().copied())
+            .map(|n| self.get_issue_state(config, n))
+            .buffer_unordered(4)
+            .collect::<Vec<_>>()
+            .await;
+
+        Ok(states.into_iter().flatten().collect())
+    }
+
+    async fn close_issue(&self, issuer: &Issuer, issue: &Issue, comment: &str) -> anyhow::Result<()> {
+        let url = issuer.config.api.get_issue_api_url(&issuer.config, issue);
+
+        let comment = serde_json::json!({ "body": comment });
+
+        let r = issuer.config.http.send(|client| {
+            client.post(format!("{url}/comments"))
+                .header("Authorization", format!("token {}", issuer.config.token()))
+                .header("Accept", "application/json")
+                .header("User-Agent", "stalkr-todo-bot")
+                .body_json(&comment)
+        }).await?;
+
+        http::into_json(r, "comment on issue").await?;
+
+        let state = serde_json::json!({ "state": "closed" });
+
+        let r = issuer.config.http.send(|client| {
+            client.patch(&url)
+                .header("Authorization", format!("token {}", issuer.config.token()))
+                .header("Accept", "application/json")
+                .header("User-Agent", "stalkr-todo-bot")
+                .body_json(&state)
+        }).await?;
+
+        http::into_json(r, "close issue").await?;
+
+        Ok(())
+    }
+
+    async fn open_pull_request(&self, config: &Config, pr: &PullRequest) -> anyhow::Result<Option<String>> {
+        let Config { owner, repo, .. } = config;
+        let url = format!("{base}/api/v1/repos/{owner}/{repo}/pulls", base = self.base_url);
+
+        let body = serde_json::json!({
+            "title": pr.title,
+            "body": pr.body,
+            "head": pr.head,
+            "base": pr.base
+        });
+
+        let r = config.http.send(|client| {
+            client.post(&url)
+                .header("Authorization", format!("token {}", config.token()))
+                .header("Accept", "application/json")
+                .header("User-Agent", "stalkr-todo-bot")
+                .body_json(&body)
+        }).await?;
+
+        let json = http::into_json(r, "open pull request").await?;
+
+        json.get("html_url")
+            .and_then(Value::as_str)
+            .map(|url| Some(url.to_owned()))
+            .context("could not parse pull request url")
+    }
+
+    async fn list_open_issues(&self, config: &Config) -> anyhow::Result<Vec<OpenIssue>> {
+        // the default maximum page size of Gitea
+        const PER_PAGE: usize = 50;
+
+        let url = self.get_issues_api_url(config);
+
+        let issues = config.http.get_all_pages(PER_PAGE, |client, page| {
+            client.get(format!("{url}?state=open&type=issues&limit={PER_PAGE}&page={page}"))
+                .header("Authorization", format!("token {}", config.token()))
+                .header("Accept", "application/json")
+                .header("User-Agent", "stalkr-todo-bot")
+        }).await?;
+
+        Ok(issues.iter().filter_map(|issue| Some(OpenIssue::new(
+            issue.get("number")?.as_u64()?,
+            issue.get("title")?.as_str()?,
+            issue.get("body").and_then(Value::as_str),
+            issue.get("labels")
+        ))).collect())
+    }
+}
@@ -0,0 +1,276 @@
+use crate::util;
+use crate::http;
+use crate::api::{Api, PullRequest};
+use crate::todo::Todo;
+use crate::config::Config;
+use crate::template::IssueText;
+use crate::meta::Milestones;
+use crate::index::OpenIssue;
+use crate::issue::{Issue, Issuer, IssueStates};
+
+use std::env;
+
+use anyhow::Context;
+use serde_json::Value;
+
+/// GitLab (gitlab.com or self-hosted) through the v4 REST api.
+///
+/// Projects are addressed by their url-encoded full path, so nested groups
+/// (`group/subgroup/project`) work, and issues by their project-scoped `iid`.
+pub struct GitlabApi {
+    /// e.g. `https://gitlab.example.com`
+    pub base_url: Box<str>,
+
+    pub token_env_var: Box<str>,
+
+    pub milestones: Milestones
+}
+
+impl GitlabApi {
+    pub const DEFAULT_BASE_URL: &str = "https://gitlab.com";
+    pub const DEFAULT_TOKEN_ENV_VAR: &str = "STALKR_GITLAB_TOKEN";
+
+    #[inline]
+    #[must_use]
+    pub fn new(base_url: &str, token_env_var: Option<&str>) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').into(),
+            token_env_var: token_env_var.unwrap_or(Self::DEFAULT_TOKEN_ENV_VAR).into(),
+            milestones: Milestones::default()
+        }
+    }
+
+    async fn list_milestones(&self, config: &Config) -> anyhow::Result<Vec<(Box<str>, u64)>> {
+        const PER_PAGE: usize = 100;
+
+        let url = format!("{project}/milestones", project = self.get_project_api_url(config));
+
+        let milestones = config.http.get_all_pages(PER_PAGE, |client, page| {
+            client.get(format!("{url}?per_page={PER_PAGE}&page={page}"))
+                .header("PRIVATE-TOKEN", config.token())
+                .header("User-Agent", "stalkr-todo-bot")
+        }).await?;
+
+        Ok(Milestones::from_json(&milestones, "id"))
+    }
+
+    #[inline]
+    fn get_project_api_url(&self, config: &Config) -> String {
+        let Config { owner, repo, .. } = config;
+        let project = util::percent_encode(&format!("{owner}/{repo}"), b"-._~");
+        format!{
+            "{base}/api/v4/projects/{project}",
+            base = self.base_url
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Api for GitlabApi {
+    #[inline(always)]
+    fn get_api_token_env_var(&self) -> &str {
+        &self.token_env_var
+    }
+
+    #[inline(always)]
+    fn get_api_token(&self) -> anyhow::Result<String> {
+        env::var(self.get_api_token_env_var()).map_err(Into::into)
+    }
+
+    #[inline(always)]
+    fn get_project_url(&self, config: &Config) -> String {
+        let Config { owner, repo, .. } = config;
+        format!{
+            "{base}/{owner}/{repo}",
+            base = self.base_url
+        }
+    }
+
+    #[inline(always)]
+    fn get_issues_api_url(&self, config: &Config) -> String {
+        format!{
+            "{project}/issues",
+            project = self.get_project_api_url(config)
+        }
+    }
+
+    #[inline(always)]
+    fn get_issue_api_url(&self, config: &Config, issue: &Issue) -> String {
+        let issue_number = issue.issue_number;
+        format!{
+            "{project}/issues/{issue_number}",
+            project = self.get_project_api_url(config)
+        }
+    }
+
+    #[inline]
+    fn get_permalink(&self, config: &Config, sha: &str, path: &str, line: u32) -> Option<String> {
+        Some(format!{
+            "{project}/-/blob/{sha}/{path}#L{line}",
+            project = self.get_project_url(config),
+            path = util::percent_encode(path, b"/-._~")
+        })
+    }
+
+    #[inline]
+    fn make_client(&self) -> surf::Result<surf::Client> {
+        Ok(surf::Client::new())
+    }
+
+    async fn post_issue(&self, issuer: &Issuer, todo: &Todo, text: &IssueText) -> anyhow::Result<u64> {
+        let mut body = todo.as_json_value(text, &issuer.config.settings.issues.labels);
+
+        // GitLab calls the body `description` and wants the labels comma-separated.
+        // Assignees are only taken by user id, so they are left out.
+        if let Some(obj) = body.as_object_mut() {
+            obj.remove("assignees");
+
+            if let Some(description) = obj.remove("body") {
+                obj.insert("description".to_owned(), description);
+            }
+
+            if let Some(Value::Array(labels)) = obj.remove("labels") {
+                let labels = labels.iter().filter_map(Value::as_str).collect::<Vec<_>>();
+                obj.insert("labels".to_owned(), Value::from(labels.join(",")));
+            }
+        }
+
+        self.milestones.resolve_in(
+            &mut body,
+            "milestone_id",
+            self.list_milestones(&issuer.config)
+        ).await;
+
+        let r = issuer.config.http.send(|client| {
+            client.post(&*issuer.issues_api_url)
+                .header("PRIVATE-TOKEN", issuer.config.token())
+                .header("User-Agent", "stalkr-todo-bot")
+                .body_json(&body)
+        }).await?;
+
+        let json = http::into_json(r, "create issue").await?;
+
+        json.get("iid").and_then(Value::as_u64).context("could not parse issue iid")
+    }
+
+    async fn check_if_issue_is_closed(&self, issuer: &Issuer, issue: &Issue) -> anyhow::Result<bool> {
+        let url = issuer.config.api.get_issue_api_url(&issuer.config, issue);
+
+        let r = issuer.config.http.send(|client| {
+            Ok(client.get(&url)
+                .header("PRIVATE-TOKEN", issuer.config.token())
+                .header("User-Agent", "stalkr-todo-bot"))
+        }).await?;
+
+        let json = http::into_json(r, "get issue").await?;
+
+        // GitLab issues are either "opened" or "closed"
+        let state = json.get("state").and_then(Value::as_str).context("could not parse issue state")?;
+
+        Ok(state == "closed")
+    }
+
+    /// Issues can be filtered by `iids[]`, up to a page of them per request
+    async fn get_issue_states(&self, config: &Config, numbers: &[u64]) -> anyhow::Result<IssueStates> {
+        const PER_PAGE: usize = 100;
+
+        let url = self.get_issues_api_url(config);
+
+        let mut states = IssueStates::default();
+
+        for chunk in numbers.chunks(PER_PAGE) {
+            let iids = chunk.iter().map(|n| format!("iids[]={n}")).collect::<Vec<_>>().join("&");
+
+            let r = config.http.send(|client| {
+                Ok(client.get(format!("{url}?{iids}&per_page={PER_PAGE}"))
+                    .header("PRIVATE-TOKEN", config.token())
+                    .header("User-Agent", "stalkr-todo-bot"))
+            }).await?;
+
+            let Value::Array(issues) = http::into_json(r, "get issue states").await? else {
+                anyhow::bail!("failed to get issue states: expected a JSON array")
+            };
+
+            states.extend(issues.iter().filter_map(|issue| Some((
+                issue.get("iid")?.as_u64()?,
+                issue.get("state")?.as_str()? == "closed"
+            ))));
+        }
+
+        Ok(states)
+    }
+
+    async fn close_issue(&self, issuer: &Issuer, issue: &Issue, comment: &str) -> anyhow::Result<()> {
+        let url = issuer.config.api.get_issue_api_url(&issuer.config, issue);
+
+        // comments are called notes
+        let comment = serde_json::json!({ "body": comment });
+
+        let r = issuer.config.http.send(|client| {
+            client.post(format!("{url}/notes"))
+                .header("PRIVATE-TOKEN", issuer.config.token())
+                .header("User-Agent", "stalkr-todo-bot")
+                .body_json(&comment)
+        }).await?;
+
+        http::into_json(r, "comment on issue").await?;
+
+        let state = serde_json::json!({ "state_event": "close" });
+
+        let r = issuer.config.http.send(|client| {
+            client.put(&url)
+                .header("PRIVATE-TOKEN", issuer.config.token())
+                .header("User-Agent", "stalkr-todo-bot")
+                .body_json(&state)
+        }).await?;
+
+        http::into_json(r, "close issue").await?;
+
+        Ok(())
+    }
+
+    /// GitLab calls them merge requests
+    async fn open_pull_request(&self, config: &Config, pr: &PullRequest) -> anyhow::Result<Option<String>> {
+        let url = format!("{project}/merge_requests", project = self.get_project_api_url(config));
+
+        let body = serde_json::json!({
+            "title": pr.title,
+            "description": pr.body,
+            "source_branch": pr.head,
+            "target_branch": pr.base
+        });
+
+        let r = config.http.send(|client| {
+            client.post(&url)
+                .header("PRIVATE-TOKEN", config.token())
+                .header("User-Agent", "stalkr-todo-bot")
+                .body_json(&body)
+        }).await?;
+
+        let json = http::into_json(r, "open merge request").await?;
+
+        json.get("web_url")
+            .and_then(Value::as_str)
+            .map(|url| Some(url.to_owned()))
+            .context("could not parse merge request url")
+    }
+
+    async fn list_open_issues(&self, config: &Config) -> anyhow::Result<Vec<OpenIssue>> {
+        const PER_PAGE: usize = 100;
+
+        let url = self.get_issues_api_url(config);
+
+        let issues = config.http.get_all_pages(PER_PAGE, |client, page| {
+            client.get(format!("{url}?state=opened&per_page={PER_PAGE}&page={page}"))
+                .header("PRIVATE-TOKEN", config.token())
+                .header("User-Agent", "stalkr-todo-bot")
+        }).await?;
+
+        Ok(issues.iter().filter_map(|issue| Some(OpenIssue::new(
+            issue.get("iid")?.as_u64()?,
+            issue.get("title")?.as_str()?,
+            issue.get("description").and_then(Value::as_str),
+            issue.get("labels")
+        ))).collect())
+    }
+}
@@ -0,0 +1,252 @@
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{Ordering, AtomicBool, AtomicUsize};
+
+use anyhow::bail;
+use serde_json::Value;
+use surf::http::Method;
+use surf::{Client, RequestBuilder, Response, StatusCode};
+use tokio::time::{self, Instant};
+
+/// Request layer shared by all issuers: waits out rate limits (pausing every
+/// in-flight request, not only the one that hit it), retries transient errors
+/// with jittered exponential backoff and aborts the run after too many failures.
+///
+/// Only idempotent requests are retried on server and network errors: a POST the
+/// tracker applied before the error would otherwise be applied twice, e.g. file an issue twice.
+/// Rate-limited requests are always retried, the tracker turned them down before doing anything.
+pub struct Http {
+    pub client: Client,
+
+    // every request waits until this instant before going out
+    paused_until: Mutex<Option<Instant>>,
+
+    failures: AtomicUsize,
+    aborted: AtomicBool
+}
+
+impl Http {
+    // attempts of a single request before it's counted as a failure
+    const MAX_ATTEMPTS: u32 = 5;
+
+    // failed requests before the whole run is aborted
+    const MAX_FAILURES: usize = 3;
+
+    const BACKOFF_BASE: Duration = Duration::from_millis(500);
+    const BACKOFF_CAP: Duration = Duration::from_secs(30);
+
+    // when the tracker says we're limited but not for how long
+    const DEFAULT_RATE_LIMIT_PAUSE: Duration = Duration::from_mins(1);
+
+    #[inline]
+    #[must_use]
+    pub const fn new(client: Client) -> Self {
+        Self {
+            client,
+            paused_until: Mutex::new(None),
+            failures: AtomicUsize::new(0),
+            aborted: AtomicBool::new(false)
+        }
+    }
+
+    #[inline(always)]
+    pub fn is_aborted(&self) -> bool {
+        self.aborted.load(Ordering::SeqCst)
+    }
+
+    /// Sends the request made by `make_rq`, remaking it for every retry.
+    ///
+    /// Returns: the first response that is neither rate-limited nor a 5xx,
+    /// a 5xx of a request that isn't safe to send again is an error right away
+    pub async fn send<F>(&self, make_rq: F) -> anyhow::Result<Response>
+    where
+        F: Fn(&Client) -> surf::Result<RequestBuilder>
+    {
+        self.send_retrying(make_rq, false).await
+    }
+
+    /// Same as [`Http::send`], for a POST that only reads, e.g. a GraphQL query,
+    /// so it's as safe to send again as a GET
+    pub async fn send_query<F>(&self, make_rq: F) -> anyhow::Result<Response>
+    where
+        F: Fn(&Client) -> surf::Result<RequestBuilder>
+    {
+        self.send_retrying(make_rq, true).await
+    }
+
+    async fn send_retrying<F>(&self, make_rq: F, is_read_only: bool) -> anyhow::Result<Response>
+    where
+        F: Fn(&Client) -> surf::Result<RequestBuilder>
+    {
+        let mut last_error = None;
+
+        for attempt in 0..Self::MAX_ATTEMPTS {
+            if self.is_aborted() {
+                bail!("aborted after {n} failed requests", n = Self::MAX_FAILURES)
+            }
+
+            self.wait_if_paused().await;
+
+            let rq = make_rq(&self.client).map_err(|e| {
+                anyhow::anyhow!("error creating request: {e}")
+            })?.build();
+
+            let is_idempotent = is_read_only || Self::is_idempotent(rq.method());
+
+            match self.client.send(rq).await {
+                Ok(r) => {
+                    if let Some(pause) = Self::get_rate_limit_pause(&r) {
+                        if Self::is_rate_limited(&r) {
+                            eprintln!{
+                                "[rate limit hit: HTTP {s}, pausing for {secs}s]",
+                                s = r.status(),
+                                secs = pause.as_secs()
+                            };
+
+                            self.pause_for(pause);
+                            last_error = Some(anyhow::anyhow!("rate limited: HTTP {}", r.status()));
+                            continue
+                        }
+
+                        // that was the last request of the window, let the others wait
+                        self.pause_for(pause);
+                    }
+
+                    if r.status().is_server_error() {
+                        last_error = Some(anyhow::anyhow!("server error: HTTP {}", r.status()));
+                        if !is_idempotent { break }
+                        self.backoff(attempt).await;
+                        continue
+                    }
+
+                    self.failures.store(0, Ordering::SeqCst);
+                    return Ok(r)
+                }
+
+                Err(e) => {
+                    last_error = Some(anyhow::anyhow!("network error: {e}"));
+                    if !is_idempotent { break }
+                    self.backoff(attempt).await;
+                }
+            }
+        }
+
+        let failures = self.failures.fetch_add(1, Ordering::SeqCst) + 1;
+        if failures >= Self::MAX_FAILURES && !self.aborted.swap(true, Ordering::SeqCst) {
+            eprintln!("[aborting: {failures} requests failed in a row]");
+        }
+
+        Err(last_error.unwrap_or_else(|| anyhow::anyhow!("request failed")))
+    }
+
+    /// GETs every page (`page` = 1, 2, ..) of a JSON array endpoint,
+    /// until a page has less than `per_page` items.
+    pub async fn get_all_pages<F>(&self, per_page: usize, make_rq: F) -> anyhow::Result<Vec<Value>>
+    where
+        F: Fn(&Client, usize) -> RequestBuilder
+    {
+        let mut items = Vec::new();
+
+        for page in 1.. {
+            let r = self.send(|client| Ok(make_rq(client, page))).await?;
+
+            let Value::Array(page_items) = into_json(r, "list issues").await? else {
+                bail!("failed to list issues: expected a JSON array")
+            };
+
+            let is_last = page_items.len() < per_page;
+
+            items.extend(page_items);
+
+            if is_last { break }
+        }
+
+        Ok(items)
+    }
+
+    #[inline]
+    const fn is_idempotent(method: Method) -> bool {
+        matches!{
+            method,
+            Method::Get | Method::Head | Method::Options | Method::Put | Method::Patch | Method::Delete
+        }
+    }
+
+    #[inline]
+    fn is_rate_limited(r: &Response) -> bool {
+        match r.status() {
+            StatusCode::TooManyRequests => true,
+
+            // GitHub also uses 403 for both primary and secondary rate limits
+            StatusCode::Forbidden => {
+                r.header("Retry-After").is_some() ||
+                Self::get_header_u64(r, &["X-RateLimit-Remaining", "RateLimit-Remaining"]) == Some(0)
+            }
+
+            _ => false
+        }
+    }
+
+    /// How long to wait before the next request, if the response says so
+    fn get_rate_limit_pause(r: &Response) -> Option<Duration> {
+        if let Some(retry_after) = r.header("Retry-After") {
+            // it may also be an http-date, which we don't bother parsing
+            return Some(retry_after.as_str().trim().parse().map_or(
+                Self::DEFAULT_RATE_LIMIT_PAUSE,
+                Duration::from_secs
+            ))
+        }
+
+        let remaining = Self::get_header_u64(r, &["X-RateLimit-Remaining", "RateLimit-Remaining"]);
+
+        if remaining != Some(0) {
+            return Self::is_rate_limited(r).then_some(Self::DEFAULT_RATE_LIMIT_PAUSE)
+        }
+
+        let Some(reset) = Self::get_header_u64(r, &["X-RateLimit-Reset", "RateLimit-Reset"]) else {
+            return Some(Self::DEFAULT_RATE_LIMIT_PAUSE)
+        };
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+
+        // +1 to not race the reset
+        Some(Duration::from_secs(reset.saturating_sub(now) + 1))
+    }
+
+    #[inline]
+    fn get_header_u64(r: &Response, names: &[&str]) -> Option<u64> {
+        names.iter().find_map(|name| r.header(*name)?.as_str().trim().parse().ok())
+    }
+
+    fn pause_for(&self, pause: Duration) {
+        let until = Instant::now() + pause;
+        let mut paused_until = self.paused_until.lock().unwrap_or_else(PoisonError::into_inner);
+        if paused_until.is_none_or(|p| p < until) {
+            *paused_until = Some(until);
+        }
+    }
+
+    async fn wait_if_paused(&self) {
+        let paused_until = *self.paused_until.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(until) = paused_until {
+            time::sleep_until(until).await;
+        }
+    }
+
+    // full jitter: uniformly random in [0, min(cap, base * 2^attempt)]
+    async fn backoff(&self, attempt: u32) {
+        let max = Self::BACKOFF_BASE.saturating_mul(1 << attempt.min(16)).min(Self::BACKOFF_CAP);
+        let delay = rand::random_range(0..=max.as_millis() as u64);
+        time::sleep(Duration::from_millis(delay)).await;
+    }
+}
+
+/// Body of a successful response as JSON, or the error the tracker replied with
+pub async fn into_json(mut r: Response, what: &str) -> anyhow::Result<Value> {
+    if !r.status().is_success() {
+        let text = r.body_string().await.unwrap_or_default();
+        bail!("failed to {what} (HTTP {s}): {text}", s = r.status())
+    }
+
+    r.body_json().await.map_err(|e| anyhow::anyhow!("failed to parse JSON response: {e}"))
+}
@@ -0,0 +1,185 @@
+use std::ops::Range;
+
+/// Lines of a file that stalkr changed, as a hunk of a unified diff without context.
+/// Used to stage only stalkr's own changes of a file that has other uncommitted changes.
+#[derive(Debug)]
+pub struct Hunk {
+    /// 1-based number of the first changed line in the index, before any of stalkr's changes
+    staged_line: usize,
+
+    /// Same in HEAD, which may differ from the index if some changes are staged
+    committed_line: usize,
+
+    old: Box<[u8]>,
+    new: Box<[u8]>
+}
+
+/// Version of a file a patch is made for
+#[derive(Eq, Copy, Clone, Debug, PartialEq)]
+pub enum Base {
+    /// The index
+    Staged,
+    /// HEAD
+    Committed
+}
+
+impl Hunk {
+    /// `range` of `buf` (the file in the work tree) replaced with `replacement`, widened to
+    /// whole lines. `maps` tell where those lines are in the index and in HEAD, see [`LineMaps`].
+    ///
+    /// Returns: `None` if the lines are part of the other uncommitted changes,
+    /// then there's no telling where they'd go without them
+    #[must_use]
+    pub fn new(buf: &[u8], range: Range<usize>, replacement: &[u8], maps: &LineMaps) -> Option<Self> {
+        let start = buf[..range.start]
+            .iter()
+            .rposition(|b| *b == b'\n')
+            .map_or(0, |i| i + 1);
+
+        // a range that ends with a newline already ends with its last line
+        let end = if range.end > start && buf[range.end - 1] == b'\n' {
+            range.end
+        } else {
+            buf[range.end..]
+                .iter()
+                .position(|b| *b == b'\n')
+                .map_or(buf.len(), |i| range.end + i + 1)
+        };
+
+        // as many lines as newlines before, plus the one after them
+        let line = buf[..start].split(|b| *b == b'\n').count();
+        let count = count_lines(&buf[start..end]);
+
+        let mut new = Vec::with_capacity(end - start + replacement.len());
+        new.extend_from_slice(&buf[start..range.start]);
+        new.extend_from_slice(replacement);
+        new.extend_from_slice(&buf[range.end..end]);
+
+        Some(Self {
+            staged_line: maps.staged.to_old(line, count)?,
+            committed_line: maps.committed.to_old(line, count)?,
+            old: buf[start..end].into(),
+            new: new.into()
+        })
+    }
+
+    #[inline(always)]
+    const fn line(&self, base: Base) -> usize {
+        match base {
+            Base::Staged => self.staged_line,
+            Base::Committed => self.committed_line
+        }
+    }
+}
+
+/// Where the lines of a file in the work tree are in an older version of it,
+/// made of the hunks of `git diff -U0` between the two
+#[derive(Debug, Default)]
+pub struct LineMap {
+    /// `(old start, old count, new start, new count)` of every hunk
+    hunks: Vec<(usize, usize, usize, usize)>
+}
+
+impl LineMap {
+    /// Parses the `@@ -a,b +c,d @@` headers of a diff without context
+    #[must_use]
+    pub fn from_diff(diff: &str) -> Self {
+        // a missing count means one line
+        let parse = |range: &str| -> Option<(usize, usize)> {
+            match range.split_once(',') {
+                Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+                None => Some((range.parse().ok()?, 1))
+            }
+        };
+
+        let hunks = diff.lines().filter_map(|line| {
+            let mut ranges = line.strip_prefix("@@ -")?.split(' ');
+            let (old_start, old_count) = parse(ranges.next()?)?;
+            let (new_start, new_count) = parse(ranges.next()?.strip_prefix('+')?)?;
+            Some((old_start, old_count, new_start, new_count))
+        }).collect();
+
+        Self { hunks }
+    }
+
+    /// Line of the old version that the `count` lines from `line` of the new one start at,
+    /// `None` if any of them were changed, or lines between them were deleted
+    #[must_use]
+    pub fn to_old(&self, line: usize, count: usize) -> Option<usize> {
+        let last = line + count.max(1) - 1;
+
+        let mut delta = 0isize;
+
+        for &(_, old_count, new_start, new_count) in &self.hunks {
+            // a deletion is listed at the line before it
+            let is_before = if new_count == 0 { new_start < line } else { new_start + new_count - 1 < line };
+
+            if is_before {
+                delta += new_count.cast_signed() - old_count.cast_signed();
+                continue
+            }
+
+            let is_inside = if new_count == 0 { new_start < last } else { new_start <= last };
+
+            if is_inside { return None }
+        }
+
+        line.checked_add_signed(-delta)
+    }
+}
+
+/// Where lines of the work tree are in the index and in HEAD
+#[derive(Debug, Default)]
+pub struct LineMaps {
+    pub staged: LineMap,
+    pub committed: LineMap
+}
+
+/// Appends the diff of `path` (relative to the work tree) made of `hunks` to `patch`,
+/// in the form `git apply --unidiff-zero` takes it, for the `base` version of the file
+pub fn write_patch(patch: &mut Vec<u8>, path: &str, hunks: &[Hunk], base: Base) {
+    if hunks.is_empty() { return }
+
+    patch.extend_from_slice(format!("diff --git a/{path} b/{path}\n--- a/{path}\n+++ b/{path}\n").as_bytes());
+
+    let mut hunks = hunks.iter().collect::<Vec<_>>();
+    hunks.sort_by_key(|h| h.line(base));
+
+    // how many lines the hunks so far added, to know where the next one starts afterwards
+    let mut delta = 0isize;
+
+    for hunk in hunks {
+        let Hunk { old, new, .. } = hunk;
+        let line = hunk.line(base);
+
+        let old_count = count_lines(old);
+        let new_count = count_lines(new);
+
+        let new_line = line.saturating_add_signed(delta);
+
+        // an empty side starts at the line before it
+        let new_start = if new_count == 0 { new_line - 1 } else { new_line };
+
+        patch.extend_from_slice(format!("@@ -{line},{old_count} +{new_start},{new_count} @@\n").as_bytes());
+
+        write_lines(patch, b'-', old);
+        write_lines(patch, b'+', new);
+
+        delta += new_count.cast_signed() - old_count.cast_signed();
+    }
+}
+
+fn count_lines(s: &[u8]) -> usize {
+    s.split_inclusive(|b| *b == b'\n').count()
+}
+
+fn write_lines(patch: &mut Vec<u8>, prefix: u8, s: &[u8]) {
+    for line in s.split_inclusive(|b| *b == b'\n') {
+        patch.push(prefix);
+        patch.extend_from_slice(line);
+
+        if !line.ends_with(b"\n") {
+            patch.extend_from_slice(b"\n\\ No newline at end of file\n");
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use crate::util;
+
+use std::path::Path;
+use std::collections::HashMap;
+
+use serde_json::Value;
+use rustc_hash::FxBuildHasher;
+
+/// Hidden marker that stalkr puts into issue bodies, e.g. `<!-- stalkr-todo: src/main.rs:42 -->`,
+/// so that the issue of a TODO can be found again if the tag never made it into the file.
+pub const LOCATION_MARKER: &str = "stalkr-todo:";
+
+#[derive(Debug)]
+pub struct OpenIssue {
+    pub number: u64,
+    pub title: Box<str>,
+
+    /// `path:line` from the location marker
+    pub location: Option<Box<str>>,
+
+    pub labels: Box<[Box<str>]>
+}
+
+impl OpenIssue {
+    /// `labels` is either an array of names or of objects with a `name`, depending on the tracker
+    #[inline]
+    #[must_use]
+    pub fn new(number: u64, title: &str, body: Option<&str>, labels: Option<&Value>) -> Self {
+        let labels = labels.and_then(Value::as_array).map(|labels| {
+            labels.iter()
+                .filter_map(|l| l.as_str().or_else(|| l.get("name")?.as_str()))
+                .map(Into::into)
+                .collect()
+        }).unwrap_or_default();
+
+        Self {
+            number,
+            title: title.into(),
+            location: body.and_then(parse_location_marker).map(Into::into),
+            labels
+        }
+    }
+
+    /// Issues get the marker since they're deduplicated, older ones may only have the labels
+    #[inline]
+    #[must_use]
+    pub fn is_created_by_stalkr(&self, default_labels: &[Box<str>]) -> bool {
+        self.location.is_some() || self.labels.iter().any(|l| default_labels.contains(l))
+    }
+}
+
+#[inline]
+#[must_use]
+pub fn make_location_marker(location: &str) -> String {
+    format!("<!-- {LOCATION_MARKER} {location} -->")
+}
+
+#[must_use]
+pub fn parse_location_marker(body: &str) -> Option<&str> {
+    let start = body.find("<!--")? + "<!--".len();
+    let rest = body[start..].trim_start().strip_prefix(LOCATION_MARKER)?;
+    let end = rest.find("-->")?;
+    Some(rest[..end].trim())
+}
+
+/// `path:line` of a TODO, with the path relative to the scanned directory
+#[inline]
+#[must_use]
+pub fn make_location(cwd: &Path, upath: &str, line_number: u32) -> String {
+    format!("{path}:{line_number}", path = util::relative_path(cwd, upath))
+}
+
+/// Open issues that were created by stalkr, looked up by title and location marker
+#[derive(Debug, Default)]
+pub struct IssueIndex {
+    by_title: HashMap<Box<str>, Vec<OpenIssue>, FxBuildHasher>
+}
+
+impl IssueIndex {
+    #[must_use]
+    pub fn new(issues: Vec<OpenIssue>) -> Self {
+        let mut by_title = HashMap::<_, Vec<_>, _>::default();
+
+        for issue in issues {
+            // without the marker there's no telling which TODO it was filed for
+            if issue.location.is_none() { continue }
+            by_title.entry(issue.title.clone()).or_default().push(issue);
+        }
+
+        Self { by_title }
+    }
+
+    #[inline]
+    fn path_of(location: &str) -> &str {
+        location.rsplit_once(':').map_or(location, |(path, _)| path)
+    }
+
+    /// Finds the issues of `todos`, `(title, location)` of the TODOs of one file.
+    /// An issue with the same title and location is theirs; failing that, e.g. when lines moved,
+    /// an issue filed from the same file, but only when neither is ambiguous: it's the only one
+    /// with that title not taken by another TODO, and the TODO is the only one with that title left.
+    #[must_use]
+    pub fn resolve(&self, todos: &[(&str, &str)]) -> Vec<Option<u64>> {
+        let mut found = todos.iter().map(|(title, location)| {
+            self.by_title.get(*title)?
+                .iter()
+                .find(|issue| issue.location.as_deref() == Some(*location))
+                .map(|issue| issue.number)
+        }).collect::<Vec<_>>();
+
+        let exact = found.iter().flatten().copied().collect::<Vec<_>>();
+
+        for i in 0..todos.len() {
+            if found[i].is_some() { continue }
+
+            let (title, location) = todos[i];
+            let path = Self::path_of(location);
+
+            let is_only_todo = todos.iter()
+                .zip(&found)
+                .filter(|((t, l), f)| f.is_none() && *t == title && Self::path_of(l) == path)
+                .count() == 1;
+
+            if !is_only_todo { continue }
+
+            let Some(candidates) = self.by_title.get(title) else { continue };
+
+            let mut candidates = candidates.iter().filter(|issue| {
+                issue.location.as_deref().map(Self::path_of) == Some(path) &&
+                !exact.contains(&issue.number)
+            });
+
+            if let (Some(issue), None) = (candidates.next(), candidates.next()) {
+                found[i] = Some(issue.number);
+            }
+        }
+
+        found
+    }
+}
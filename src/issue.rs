@@ -1,5 +1,8 @@
 use crate::util;
+use crate::index;
+use crate::template::IssueText;
 use crate::todo::Todo;
+use crate::close::Orphan;
 use crate::prompt::Prompt;
 use crate::config::Config;
 use crate::mode::ModeValue;
@@ -8,9 +11,11 @@ use crate::purge::{Purge, Purges};
 use crate::tag::{Tag, InserterValue};
 
 use std::sync::Arc;
+use std::collections::HashMap;
 use std::sync::atomic::{Ordering, AtomicUsize};
 
 use futures::{stream, StreamExt};
+use rustc_hash::FxBuildHasher;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 
@@ -18,6 +23,9 @@ pub struct Issue {
     pub issue_number: u64
 }
 
+/// Whether an issue is closed, by issue number
+pub type IssueStates = HashMap<u64, bool, FxBuildHasher>;
+
 pub type IssueValue = ModeValue;
 
 #[derive(Clone)]
@@ -35,8 +43,7 @@ pub struct Issuer {
     pub processed_count: Arc<AtomicUsize>,
     pub config: Arc<Config>,
     pub fm: Arc<FileManager>,
-    pub max_http_concurrency: usize,
-    pub rq_client: surf::Client
+    pub max_http_concurrency: usize
 }
 
 impl Issuer {
@@ -53,9 +60,6 @@ impl Issuer {
             processed_count: Arc<AtomicUsize>,
             max_http_concurrency: usize,
         ) -> Self {
-            let rq_client = config.api.make_client(&config)
-                .expect("failed to build API client");
-
             let issues_api_url = Arc::from(config.api.get_issues_api_url(&config));
 
             Self {
@@ -65,8 +69,7 @@ impl Issuer {
                 processed_count,
                 config,
                 fm,
-                max_http_concurrency,
-                rq_client
+                max_http_concurrency
             }
         }
     }
@@ -81,10 +84,14 @@ impl Issuer {
                     (ModeValue::Reporting(todos), IssuerTx::Inserter(inserter_tx)) => {
                         let file_id = todos[0].loc.file_id();
 
-                        stream::iter(todos.into_iter()).for_each_concurrent(4, |todo| {
+                        // resolved together, so that TODOs with the same title don't take each other's issue
+                        let texts = todos.iter().map(|todo| issuer.render_text(todo)).collect::<Vec<_>>();
+                        let existing = issuer.find_existing_issues(&texts);
+
+                        stream::iter(todos.into_iter().zip(texts).zip(existing)).for_each_concurrent(4, |((todo, text), existing)| {
                             let issuer = issuer.clone();
                             async move {
-                                issuer.post_todo(todo).await;
+                                issuer.post_todo(todo, text, existing).await;
                             }
                         }).await;
 
@@ -94,11 +101,21 @@ impl Issuer {
                     }
 
                     (ModeValue::Purging(purges), IssuerTx::Prompter(prompter_tx)) => {
-                        let closed = stream::iter(purges.purges.into_iter())
+                        let closed = stream::iter(purges.purges)
                             .map(|purge| {
                                 let issuer = self.clone();
                                 async move {
-                                    let is_closed = issuer.check_if_purge_needed(&purge).await;
+                                    let is_closed = match issuer.check_if_purge_needed(&purge).await {
+                                        Ok(is_closed) => is_closed,
+                                        Err(e) => {
+                                            // better to leave it in place than to guess
+                                            eprintln!{
+                                                "[couldn't check issue #{n}, keeping it: {e:#}]",
+                                                n = purge.tag.issue_number
+                                            };
+                                            false
+                                        }
+                                    };
                                     (purge, is_closed)
                                 }
                             })
@@ -127,13 +144,34 @@ impl Issuer {
                         }
                     }
 
+                    (ModeValue::Closing(orphans), IssuerTx::None) => {
+                        stream::iter(orphans).for_each_concurrent(4, |orphan| {
+                            let issuer = issuer.clone();
+                            async move {
+                                issuer.close_orphan(orphan).await;
+                            }
+                        }).await;
+                    }
+
                     _ => unreachable!("unreachable tx-value combination")
                 }
             }
         }).await;
     }
 
-    async fn check_if_purge_needed(&self, purge: &Purge) -> bool {
+    async fn check_if_purge_needed(&self, purge: &Purge) -> anyhow::Result<bool> {
+        let cached = self.config.issue_states
+            .get()
+            .and_then(|states| states.get(&purge.tag.issue_number).copied());
+
+        if let Some(is_closed) = cached {
+            if is_closed {
+                self.config.found_closed_todo.store(true, Ordering::SeqCst);
+            }
+
+            return Ok(is_closed)
+        }
+
         if !self.config.found_closed_todo.load(Ordering::SeqCst) {
             let line_number = purge.tag.todo.loc.line_number();
             let file_path = self.fm.get_file_path_unchecked(purge.tag.todo.loc.file_id());
@@ -159,13 +197,58 @@ impl Issuer {
             println!("[checking if TODO at {prefix}{dots_after_issue}is closed..]");
         }
 
-        self.config.api.check_if_issue_is_closed(
+        let is_closed = self.config.api.check_if_issue_is_closed(
             self,
             &Issue { issue_number: purge.tag.issue_number }
-        ).await
+        ).await?;
+
+        if is_closed {
+            self.config.found_closed_todo.store(true, Ordering::SeqCst);
+        }
+
+        Ok(is_closed)
+    }
+
+    async fn close_orphan(&self, orphan: Orphan) {
+        let n = orphan.issue.number;
+
+        let closed = self.config.api.close_issue(
+            self,
+            &Issue { issue_number: n },
+            &orphan.comment
+        ).await;
+
+        match closed {
+            Ok(()) => {
+                println!("[closed #{n}: {title}]", title = orphan.issue.title);
+                self.processed_count.fetch_add(1, Ordering::SeqCst);
+            }
+
+            Err(e) => eprintln!("[failed to close issue #{n}: {e:#}]")
+        }
+    }
+
+    fn render_text(&self, todo: &Todo) -> IssueText {
+        let location = index::make_location(
+            &self.config.cwd,
+            &self.fm.get_file_path_unchecked(todo.loc.file_id()),
+            todo.loc.line_number()
+        );
+
+        IssueText::render(&self.config, &self.fm, todo, location)
     }
 
-    async fn post_todo(&self, todo: Todo) {
+    fn find_existing_issues(&self, texts: &[IssueText]) -> Vec<Option<u64>> {
+        let Some(index) = self.config.issue_index.get() else {
+            return vec![None; texts.len()]
+        };
+
+        let todos = texts.iter().map(|t| (t.title.as_str(), t.location.as_str())).collect::<Vec<_>>();
+
+        index.resolve(&todos)
+    }
+
+    async fn post_todo(&self, todo: Todo, text: IssueText, existing: Option<u64>) {
         if self.config.simulate_reporting {
             // simulate network latency
             use tokio::time::{sleep, Duration};
@@ -183,7 +266,25 @@ impl Issuer {
             return
         }
 
-        self.config.api.post_issue(self, todo).await;
+        if let Some(issue_number) = existing {
+            println!("[{location}: reusing existing issue #{issue_number}]", location = text.location);
+
+            let file_id = todo.loc.file_id();
+            let tag = Tag { issue_number, todo };
+            self.fm.add_tag_to_file(file_id, tag);
+
+            return
+        }
+
+        match self.config.api.post_issue(self, &todo, &text).await {
+            Ok(issue_number) => {
+                let file_id = todo.loc.file_id();
+                let tag = Tag { issue_number, todo };
+                self.fm.add_tag_to_file(file_id, tag);
+            }
+
+            Err(e) => eprintln!("[failed to create issue for {location}: {e:#}]", location = text.location)
+        }
     }
 }
 
@@ -0,0 +1,88 @@
+use crate::util;
+
+use std::fmt;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub struct Keyword {
+    pub name: Box<str>,
+
+    /// Labels added to issues created from this keyword
+    pub labels: Box<[Box<str>]>,
+
+    /// Prepended to the title of issues created from this keyword, e.g. `[FIXME] `
+    pub title_prefix: Option<Box<str>>
+}
+
+impl fmt::Display for Keyword {
+    #[inline(always)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+impl Keyword {
+    pub const DEFAULT: &str = "TODO";
+
+    #[inline]
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: util::string_into_boxed_str_norealloc(name.to_owned()),
+            labels: Box::new([]),
+            title_prefix: None
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_valid_name(name: &str) -> bool {
+        !name.is_empty() && !name.contains(|c: char| c.is_whitespace() || matches!(c, ':' | '(' | '['))
+    }
+}
+
+/// Set of keywords the scanner looks for, sorted longest-first
+/// so that e.g. `TODOC` wins over `TODO`.
+#[derive(Debug)]
+pub struct Keywords(Box<[Arc<Keyword>]>);
+
+impl Default for Keywords {
+    #[inline]
+    fn default() -> Self {
+        Self::new(vec![Keyword::new(Keyword::DEFAULT)])
+    }
+}
+
+impl Keywords {
+    #[inline]
+    #[must_use]
+    pub fn new(mut keywords: Vec<Keyword>) -> Self {
+        keywords.sort_by_key(|k| std::cmp::Reverse(k.name.len()));
+        keywords.dedup_by(|a, b| a.name == b.name);
+
+        Self(keywords.into_iter().map(Arc::new).collect())
+    }
+
+    #[inline(always)]
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<Keyword>> {
+        self.0.iter()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Arc<Keyword>> {
+        self.0.iter().find(|k| &*k.name == name)
+    }
+
+    /// Returns the keyword `s` starts with, if it's followed by either `:`, `(` or `[`,
+    /// optionally after some spaces (e.g. `TODO : fix`)
+    #[inline]
+    #[must_use]
+    pub fn match_start(&self, s: &str) -> Option<&Arc<Keyword>> {
+        self.0.iter().find(|k| {
+            s.strip_prefix(&*k.name).is_some_and(|rest| {
+                rest.trim_start_matches([' ', '\t']).starts_with([':', '(', '['])
+            })
+        })
+    }
+}
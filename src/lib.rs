@@ -35,17 +35,34 @@ static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 pub mod util;
 
 pub mod gh;
+pub mod http;
+pub mod gl;
+pub mod gitea;
+pub mod local;
 pub mod fm;
 pub mod git;
+pub mod hunk;
 pub mod loc;
 pub mod tag;
 pub mod cli;
+pub mod check;
+pub mod baseline;
 pub mod api;
 pub mod mode;
 pub mod todo;
+pub mod meta;
+pub mod template;
 pub mod issue;
+pub mod index;
 pub mod purge;
+pub mod close;
+pub mod sarif;
+pub mod export;
+pub mod sniff;
 pub mod stalk;
 pub mod config;
+pub mod keyword;
+pub mod settings;
 pub mod prompt;
+pub mod syntax;
 pub mod comment;
@@ -0,0 +1,381 @@
+use crate::util;
+use crate::api::Api;
+use crate::todo::Todo;
+use crate::config::Config;
+use crate::template::IssueText;
+use crate::index::OpenIssue;
+use crate::issue::{Issue, Issuer, IssueStates};
+
+use std::io::{self, Write};
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context};
+
+/// Offline issue tracker that keeps issues as markdown files with a front-matter:
+///
+/// ```text
+/// .stalkr/issues/12.md
+/// ---
+/// title: "handle errors"
+/// state: open
+/// created: 2025-01-31T12:00:00Z
+/// location: "src/main.rs:42"
+/// labels: ["from-todo", "bug"]
+/// assignees: ["alice"]
+/// ---
+///
+/// description
+/// ```
+pub struct LocalApi {
+    pub issues_dir: PathBuf,
+
+    // serializes number allocation between concurrent posts
+    alloc_lock: Mutex<()>
+}
+
+#[derive(Eq, Copy, Clone, Debug, PartialEq)]
+pub enum State {
+    Open,
+    Closed
+}
+
+impl State {
+    #[inline(always)]
+    #[must_use]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Open   => "open",
+            Self::Closed => "closed"
+        }
+    }
+}
+
+impl LocalApi {
+    pub const ISSUES_DIR: &str = ".stalkr/issues";
+
+    // last allocated number, so numbers of deleted issues are never reused
+    const LAST_FILE_NAME: &str = ".last";
+
+    #[inline]
+    #[must_use]
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            issues_dir: dir.join(Self::ISSUES_DIR),
+            alloc_lock: Mutex::new(())
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn get_issue_path(&self, issue_number: u64) -> PathBuf {
+        self.issues_dir.join(format!("{issue_number}.md"))
+    }
+
+    /// Writes a new issue file and returns its number
+    pub fn create_issue(&self, contents: &str) -> anyhow::Result<u64> {
+        let _guard = self.alloc_lock.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        fs::create_dir_all(&self.issues_dir).with_context(|| {
+            format!("couldn't create {}", self.issues_dir.display())
+        })?;
+
+        let last_path = self.issues_dir.join(Self::LAST_FILE_NAME);
+
+        let last = match fs::read_to_string(&last_path) {
+            Ok(s) => s.trim().parse::<u64>().with_context(|| {
+                format!("corrupted {}", last_path.display())
+            })?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e).with_context(|| format!("couldn't read {}", last_path.display()))
+        };
+
+        let mut issue_number = last.max(self.max_issue_number()?) + 1;
+
+        // `create_new` so that another stalkr process can't hand out the same number
+        let mut file = loop {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(self.get_issue_path(issue_number))
+            {
+                Ok(file) => break file,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => issue_number += 1,
+                Err(e) => return Err(e).context("couldn't create issue file")
+            }
+        };
+
+        file.write_all(contents.as_bytes()).context("couldn't write issue file")?;
+
+        fs::write(&last_path, format!("{issue_number}\n")).with_context(|| {
+            format!("couldn't write {}", last_path.display())
+        })?;
+
+        Ok(issue_number)
+    }
+
+    fn max_issue_number(&self) -> anyhow::Result<u64> {
+        let entries = fs::read_dir(&self.issues_dir).with_context(|| {
+            format!("couldn't read {}", self.issues_dir.display())
+        })?;
+
+        Ok(entries.filter_map(|e| {
+            let name = e.ok()?.file_name();
+            name.to_str()?.strip_suffix(".md")?.parse::<u64>().ok()
+        }).max().unwrap_or(0))
+    }
+
+    pub fn get_state(&self, issue_number: u64) -> anyhow::Result<State> {
+        let path = self.get_issue_path(issue_number);
+
+        let contents = fs::read_to_string(&path).with_context(|| {
+            format!("couldn't read issue #{issue_number} at {}", path.display())
+        })?;
+
+        match Self::front_matter_value(&contents, "state") {
+            Some("open")   => Ok(State::Open),
+            Some("closed") => Ok(State::Closed),
+            Some(state)    => bail!("{}: unknown state: {state:?}", path.display()),
+            None           => bail!("{}: missing `state:` in the front-matter", path.display())
+        }
+    }
+
+    pub fn set_state(&self, issue_number: u64, state: State) -> anyhow::Result<()> {
+        let path = self.get_issue_path(issue_number);
+
+        let contents = fs::read_to_string(&path).with_context(|| {
+            format!("couldn't read issue #{issue_number} at {}", path.display())
+        })?;
+
+        let Some((start, end)) = Self::front_matter_line(&contents, "state") else {
+            bail!("{}: missing `state:` in the front-matter", path.display())
+        };
+
+        let mut new_contents = String::with_capacity(contents.len());
+        new_contents.push_str(&contents[..start]);
+        new_contents.push_str("state: ");
+        new_contents.push_str(state.as_str());
+        new_contents.push_str(&contents[end..]);
+
+        fs::write(&path, new_contents).with_context(|| format!("couldn't write {}", path.display()))
+    }
+
+    /// Comments go after the description, separated by a rule
+    pub fn add_comment(&self, issue_number: u64, comment: &str) -> anyhow::Result<()> {
+        let path = self.get_issue_path(issue_number);
+
+        let mut file = OpenOptions::new().append(true).open(&path).with_context(|| {
+            format!("couldn't open issue #{issue_number} at {}", path.display())
+        })?;
+
+        write!(file, "\n---\n\n{comment}\n").with_context(|| format!("couldn't write {}", path.display()))
+    }
+
+    /// Byte range of the `key:` line of the front-matter, without the line ending
+    fn front_matter_line(contents: &str, key: &str) -> Option<(usize, usize)> {
+        let mut lines = contents.split_inclusive('\n');
+        let mut offset = lines.next().filter(|l| l.trim_end() == "---")?.len();
+
+        for line in lines {
+            let start = offset;
+            offset += line.len();
+
+            let line = line.trim_end();
+            if line == "---" { break }
+
+            if line.strip_prefix(key).is_some_and(|rest| rest.starts_with(':')) {
+                return Some((start, start + line.len()))
+            }
+        }
+
+        None
+    }
+
+    fn front_matter_value<'a>(contents: &'a str, key: &str) -> Option<&'a str> {
+        let (start, end) = Self::front_matter_line(contents, key)?;
+        let value = contents[start + key.len() + 1..end].trim();
+        Some(value.trim_matches('"'))
+    }
+
+    // strings are written as JSON strings, see `make_issue_contents`
+    fn front_matter_string(contents: &str, key: &str) -> Option<String> {
+        let (start, end) = Self::front_matter_line(contents, key)?;
+        let value = contents[start + key.len() + 1..end].trim();
+        serde_json::from_str(value).ok().or_else(|| Some(value.to_owned()))
+    }
+
+    fn make_issue_contents(todo: &Todo, text: &IssueText, default_labels: &[Box<str>]) -> String {
+        // JSON strings are valid YAML scalars
+        let quote = |s: &str| serde_json::to_string(s).unwrap_or_default();
+
+        let mut contents = format!{
+            "---\ntitle: {title}\nstate: {state}\ncreated: {created}\nlocation: {location}\n",
+            title = quote(&text.title),
+            state = State::Open.as_str(),
+            created = Self::now_rfc3339(),
+            location = quote(&text.location)
+        };
+
+        let labels = todo.issue_labels(default_labels);
+        if !labels.is_empty() {
+            contents.push_str("labels: ");
+            contents.push_str(&serde_json::json!(labels).to_string());
+            contents.push('\n');
+        }
+
+        if !todo.meta.assignees.is_empty() {
+            contents.push_str("assignees: ");
+            contents.push_str(&serde_json::json!(todo.meta.assignees).to_string());
+            contents.push('\n');
+        }
+
+        if let Some(milestone) = &todo.meta.milestone {
+            contents.push_str("milestone: ");
+            contents.push_str(&quote(milestone));
+            contents.push('\n');
+        }
+
+        contents.push_str("---\n");
+
+        if !text.body.is_empty() {
+            contents.push('\n');
+            contents.push_str(&text.body);
+            contents.push('\n');
+        }
+
+        contents
+    }
+
+    // UTC, e.g. `2025-01-31T12:00:00Z`
+    fn now_rfc3339() -> String {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+
+        let (days, rem) = (secs / 86400, secs % 86400);
+        let (hour, min, sec) = (rem / 3600, rem % 3600 / 60, rem % 60);
+
+        // days since the epoch -> civil date, see http://howardhinnant.github.io/date_algorithms.html
+        let shifted = days + 719_468;
+        let era = shifted / 146_097;
+        let doe = shifted - era * 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + u64::from(month <= 2);
+
+        format!("{year:04}-{month:02}-{day:02}T{hour:02}:{min:02}:{sec:02}Z")
+    }
+}
+
+#[async_trait::async_trait]
+impl Api for LocalApi {
+    #[inline(always)]
+    fn get_api_token_env_var(&self) -> &'static str {
+        ""
+    }
+
+    #[inline(always)]
+    fn get_api_token(&self) -> anyhow::Result<String> {
+        Ok(String::new())
+    }
+
+    #[inline(always)]
+    fn needs_token(&self) -> bool {
+        false
+    }
+
+    #[inline(always)]
+    fn get_project_url(&self, _config: &Config) -> String {
+        self.issues_dir.display().to_string()
+    }
+
+    #[inline(always)]
+    fn get_issues_api_url(&self, _config: &Config) -> String {
+        self.issues_dir.display().to_string()
+    }
+
+    #[inline(always)]
+    fn get_issue_api_url(&self, _config: &Config, issue: &Issue) -> String {
+        self.get_issue_path(issue.issue_number).display().to_string()
+    }
+
+    #[inline]
+    fn make_client(&self) -> surf::Result<surf::Client> {
+        Ok(surf::Client::new())
+    }
+
+    async fn post_issue(&self, issuer: &Issuer, todo: &Todo, text: &IssueText) -> anyhow::Result<u64> {
+        let contents = Self::make_issue_contents(
+            todo,
+            text,
+            &issuer.config.settings.issues.labels
+        );
+        self.create_issue(&contents)
+    }
+
+    async fn check_if_issue_is_closed(&self, _issuer: &Issuer, issue: &Issue) -> anyhow::Result<bool> {
+        Ok(self.get_state(issue.issue_number)? == State::Closed)
+    }
+
+    async fn get_issue_states(&self, _config: &Config, numbers: &[u64]) -> anyhow::Result<IssueStates> {
+        Ok(numbers.iter().filter_map(|n| {
+            self.get_state(*n).ok().map(|state| (*n, state == State::Closed))
+        }).collect())
+    }
+
+    async fn close_issue(&self, _issuer: &Issuer, issue: &Issue, comment: &str) -> anyhow::Result<()> {
+        self.add_comment(issue.issue_number, comment)?;
+        self.set_state(issue.issue_number, State::Closed)
+    }
+
+    async fn list_open_issues(&self, _config: &Config) -> anyhow::Result<Vec<OpenIssue>> {
+        let entries = match fs::read_dir(&self.issues_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| {
+                format!("couldn't read {}", self.issues_dir.display())
+            })
+        };
+
+        let mut issues = Vec::new();
+
+        for entry in entries {
+            let path = entry?.path();
+
+            let Some(number) = path.file_name()
+                .and_then(|n| n.to_str()?.strip_suffix(".md")?.parse::<u64>().ok())
+            else {
+                continue
+            };
+
+            let contents = fs::read_to_string(&path).with_context(|| {
+                format!("couldn't read {}", path.display())
+            })?;
+
+            if Self::front_matter_value(&contents, "state") != Some(State::Open.as_str()) {
+                continue
+            }
+
+            let title = Self::front_matter_string(&contents, "title").unwrap_or_default();
+
+            let labels = Self::front_matter_value(&contents, "labels")
+                .and_then(|labels| serde_json::from_str(labels).ok())
+                .and_then(|labels| util::json_str_array(&labels))
+                .unwrap_or_default();
+
+            issues.push(OpenIssue {
+                number,
+                title: title.into(),
+                location: Self::front_matter_string(&contents, "location").map(Into::into),
+                labels
+            });
+        }
+
+        Ok(issues)
+    }
+}
@@ -1,28 +1,41 @@
-// TODO(#32): Implement closing mode
 // TODO(#38): Don't trim_start the lines of descriptions
-// TODO(#39): Allow for `gitdir` redirections in .git
 
-use stalkr::cli::Cli;
-use stalkr::mode::Mode;
+use stalkr::cli::{Cli, Commands, IssueAction};
+use stalkr::close;
+use stalkr::api::PullRequest;
+use stalkr::git::CommitStrategy;
+use stalkr::mode::{Mode, ModeValue};
+use stalkr::check::Checker;
+use stalkr::baseline::Baseline;
 use stalkr::config::Config;
 use stalkr::fm::FileManager;
+use stalkr::index::IssueIndex;
+use stalkr::local::{LocalApi, State};
+use stalkr::export::{Format, Exporter};
 use stalkr::tag::TagInserter;
 use stalkr::stalk::{Stalkr, StalkrTx};
 use stalkr::issue::{Issuer, IssuerTx};
-use stalkr::prompt::{Prompter, PrompterTx};
+use stalkr::prompt::{Prompt, Prompter, PrompterTx};
 
 use std::thread;
+use std::fmt::Write;
 use std::sync::Arc;
+use std::collections::HashSet;
 use std::process::{exit, ExitCode};
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 use clap::Parser;
-use tokio::sync::mpsc::unbounded_channel;
+use rustc_hash::FxBuildHasher;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 
 #[tokio::main]
 async fn main() -> ExitCode {
     let cli = Cli::parse();
 
+    if let Some(Commands::Issue { action }) = &cli.command {
+        return managing_local_issue(&cli, action)
+    }
+
     let config = match Config::new(&cli) {
         Ok(cfg) => Arc::new(cfg),
         Err(e) => {
@@ -66,6 +79,8 @@ async fn main() -> ExitCode {
             let found_count     = found_count.load(Ordering::Acquire);
             let processed_count = processed_count.load(Ordering::Acquire);
             println!();
+            // nothing was committed yet with per-run commits
+            config.git_locker.undo_pending();
             config.mode.print_finish_msg(found_count, processed_count);
             exit(0);
         }
@@ -73,6 +88,35 @@ async fn main() -> ExitCode {
 
     let fm = Arc::new(FileManager::default());
 
+    if let (Mode::Listing, Some(format)) = (config.mode, config.list_format) {
+        return exporting(
+            fm,
+            config,
+            format,
+            found_count,
+            processed_count
+        ).await
+    }
+
+    if config.mode == Mode::Checking {
+        return checking(
+            fm,
+            config,
+            found_count,
+            processed_count
+        ).await
+    }
+
+    if config.mode == Mode::Closing {
+        return closing(
+            fm,
+            config,
+            found_count,
+            processed_count,
+            max_http_concurrency
+        ).await
+    }
+
     if config.mode == Mode::Listing {
         listing(
             fm,
@@ -81,7 +125,7 @@ async fn main() -> ExitCode {
             processed_count
         ).await
     } else {
-        reporting_and_purging(
+        return reporting_and_purging(
             fm,
             config,
             found_count,
@@ -101,7 +145,7 @@ async fn reporting_and_purging(
     processed_count: Arc<AtomicUsize>,
     num_cpus: usize,
     max_http_concurrency: usize
-) {
+) -> ExitCode {
     // ---------------------- worker channels ----------------------
 
     // stalkr workers  -> prompter thread
@@ -113,6 +157,32 @@ async fn reporting_and_purging(
     // issue workers   -> inserter workers
     let (inserter_tx, inserter_rx) = unbounded_channel();
 
+    // before anything gets edited
+    if !config.simulate_reporting {
+        config.git_locker.read_status();
+    }
+
+    // all commits go onto the new branch, made once there's something to commit
+    if let Some(branch) = config.branch.as_deref().filter(|_| !config.simulate_reporting) {
+        if config.head.branch.is_none() {
+            eprintln!("[can't open a pull request from a detached HEAD, check out a branch first]");
+            return ExitCode::FAILURE
+        }
+
+        if let Err(e) = config.git_locker.commit_onto_branch(branch) {
+            eprintln!("[couldn't create branch {branch}: {e:#}]");
+            return ExitCode::FAILURE
+        }
+    }
+
+    // the prompter already needs to know which todoʼs were filed before
+    if config.mode == Mode::Reporting && !config.simulate_reporting {
+        match config.api.list_open_issues(&config).await {
+            Ok(issues) => _ = config.issue_index.set(IssueIndex::new(issues)),
+            Err(e) => eprintln!("[couldn't fetch open issues, duplicates won't be detected: {e:#}]")
+        }
+    }
+
     // ---------------------- workers spawns ----------------------
 
     let prompter_task = Prompter::spawn(
@@ -121,19 +191,26 @@ async fn reporting_and_purging(
         match config.mode {
             Mode::Purging   => PrompterTx::Inserter(inserter_tx.clone()),
             Mode::Reporting => PrompterTx::Issuer(issue_tx.clone()),
-            Mode::Listing   => unreachable!(),
+            Mode::Listing | Mode::Checking | Mode::Closing => unreachable!(),
         },
         processed_count.clone(),
         prompter_rx
     );
 
+    // stalkr workers -> issue state resolver, purging only
+    let (resolver_tx, resolver_rx) = unbounded_channel();
+
     let stalkr_task = Stalkr::spawn(
         fm.clone(),
         config.clone(),
         match config.mode {
-            Mode::Purging   => StalkrTx::Issuer(issue_tx.clone()),
-            Mode::Reporting => StalkrTx::Prompter(prompter_tx.clone()),
-            Mode::Listing   => unreachable!(),
+            Mode::Purging   => StalkrTx::Issuer(resolver_tx),
+            Mode::Reporting => {
+                // so that the resolver sees EOF right away
+                drop(resolver_tx);
+                StalkrTx::Prompter(prompter_tx.clone())
+            }
+            Mode::Listing | Mode::Checking | Mode::Closing => unreachable!(),
         },
         found_count.clone()
     );
@@ -142,7 +219,7 @@ async fn reporting_and_purging(
         match config.mode {
             Mode::Purging   => IssuerTx::Prompter(prompter_tx.clone()),
             Mode::Reporting => IssuerTx::Inserter(inserter_tx.clone()),
-            Mode::Listing   => unreachable!()
+            Mode::Listing | Mode::Checking | Mode::Closing => unreachable!()
         },
         config.clone(),
         fm.clone(),
@@ -152,6 +229,13 @@ async fn reporting_and_purging(
         issue_rx
     );
 
+    // the whole scan is needed to look the issues up at once, so purges wait here
+    let resolver_task = tokio::spawn(resolve_issue_states(
+        config.clone(),
+        resolver_rx,
+        issue_tx.clone()
+    ));
+
     let inserter_task = TagInserter::spawn(
         fm,
         config.clone(),
@@ -167,14 +251,16 @@ async fn reporting_and_purging(
     drop(inserter_tx);
 
     // ---------------------- await all tasks in parallel ----------------------
-    let (stalkr_res, issue_res, prompter_res, inserter_res) = tokio::join!{
+    let (stalkr_res, resolver_res, issue_res, prompter_res, inserter_res) = tokio::join!{
         stalkr_task,
+        resolver_task,
         issue_task,
         prompter_task,
         inserter_task
     };
 
     stalkr_res.expect("[could not await parsing workers]");
+    resolver_res.expect("[could not await issue state resolver]");
     issue_res.expect("[could not await issuing workers]");
     prompter_res.expect("[could not await prompting thread]");
     inserter_res.expect("[could not await tag inserting workers]");
@@ -183,6 +269,121 @@ async fn reporting_and_purging(
     let processed_count = processed_count.load(Ordering::Acquire);
 
     config.mode.print_finish_msg(found_count, processed_count);
+
+    let committed = config.git_locker.commit_run(|n| match config.mode {
+        Mode::Purging => format!("Remove {n} closed TODOs"),
+        _             => format!("Add {n} issue tags")
+    });
+
+    if let Err(e) = committed {
+        eprintln!("[couldn't commit the changes: {e:#}]");
+        return ExitCode::FAILURE
+    }
+
+    if config.http.is_aborted() {
+        eprintln!("[aborted: the issue tracker kept failing, the rest was left untouched]");
+        return ExitCode::FAILURE
+    }
+
+    if let Err(e) = opening_pull_request(&config, processed_count).await {
+        eprintln!("[couldn't open a pull request: {e:#}]");
+        return ExitCode::FAILURE
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// Pushes `--branch` and opens a pull request listing the issues of the inserted or removed tags
+async fn opening_pull_request(config: &Config, processed_count: usize) -> anyhow::Result<()> {
+    let (Some(head), Some(base)) = (config.branch.as_deref(), config.head.branch.as_deref()) else {
+        return Ok(())
+    };
+
+    if config.simulate_reporting || processed_count == 0 {
+        return Ok(())
+    }
+
+    if config.settings.commit == CommitStrategy::None {
+        eprintln!("[the changes aren't committed (`\"commit\": \"none\"`), so {head} isn't made]");
+        return Ok(())
+    }
+
+    if !config.git_locker.is_on_branch() {
+        eprintln!("[nothing was committed, so {head} isn't made]");
+        return Ok(())
+    }
+
+    config.git_locker.push(&config.remote, head)?;
+
+    println!("[pushed {head} to {remote}]", remote = config.remote);
+
+    let mut issues = std::mem::take(&mut *config.tagged_issues.lock().unwrap());
+    issues.sort_unstable_by_key(|(n, _)| *n);
+    issues.dedup_by_key(|(n, _)| *n);
+
+    let (title, intro) = match config.mode {
+        Mode::Purging => (
+            format!("Remove {n} closed TODOs", n = issues.len()),
+            "Removes the TODOs of these closed issues:"
+        ),
+        _ => (
+            format!("Add issue tags to {n} TODOs", n = issues.len()),
+            "Tags the TODOs with the issues that were created for them:"
+        )
+    };
+
+    let mut body = format!("{intro}\n\n");
+    for (n, title) in &issues {
+        _ = writeln!(body, "- #{n} {title}");
+    }
+
+    let pr = PullRequest { head, base, title, body };
+
+    match config.api.open_pull_request(config, &pr).await? {
+        Some(url) => println!("[opened pull request]: {url}"),
+        None => println!("[the tracker has no pull requests, merge {head} into {base} yourself]")
+    }
+
+    Ok(())
+}
+
+/// Looks up the states of all tagged issues in bulk, instead of one request per tag,
+/// then passes the purges on. Issues it couldn't resolve get checked one by one by [`Issuer`].
+async fn resolve_issue_states(
+    config: Arc<Config>,
+    mut resolver_rx: UnboundedReceiver<ModeValue>,
+    issue_tx: UnboundedSender<ModeValue>
+) {
+    let mut mode_values = Vec::new();
+    while let Some(mode_value) = resolver_rx.recv().await {
+        mode_values.push(mode_value);
+    }
+
+    let mut numbers = mode_values.iter()
+        .filter_map(|mode_value| match mode_value {
+            ModeValue::Purging(purges) => Some(purges.iter().map(|p| p.tag.issue_number)),
+            _ => None
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+
+    numbers.sort_unstable();
+    numbers.dedup();
+
+    if !numbers.is_empty() {
+        println!("[checking states of {n} issues..]", n = numbers.len());
+
+        match config.api.get_issue_states(&config, &numbers).await {
+            Ok(states) => _ = config.issue_states.set(states),
+            Err(e) => eprintln!("[couldn't check issues in bulk, checking one by one: {e:#}]")
+        }
+    }
+
+    for mode_value in mode_values {
+        if issue_tx.send(mode_value).is_err() {
+            eprintln!("[failed to send purges to issue workers]");
+        }
+    }
 }
 
 async fn listing(
@@ -220,3 +421,213 @@ async fn listing(
 
     config.mode.print_finish_msg(found_count, processed_count);
 }
+
+async fn exporting(
+    fm: Arc<FileManager>,
+    config: Arc<Config>,
+    format: Format,
+    found_count: Arc<AtomicUsize>,
+    processed_count: Arc<AtomicUsize>,
+) -> ExitCode {
+    let exporter = match Exporter::new(
+        fm.clone(),
+        config.clone(),
+        format,
+        config.output.as_deref(),
+        processed_count.clone()
+    ) {
+        Ok(exporter) => exporter,
+        Err(e) => {
+            eprintln!("[could not open output file]: {e}");
+            return ExitCode::FAILURE
+        }
+    };
+
+    // stalkr workers -> exporter
+    let (listing_tx, listing_rx) = unbounded_channel();
+
+    let exporter_task = exporter.spawn(listing_rx);
+
+    let stalkr_task = Stalkr::spawn(
+        fm,
+        config.clone(),
+        StalkrTx::Listing(listing_tx),
+        found_count.clone()
+    );
+
+    let (stalkr_res, exporter_res) = tokio::join!(stalkr_task, exporter_task);
+    stalkr_res.expect("[could not await parsing workers]");
+    exporter_res.expect("[could not await exporter]");
+
+    // stdout is reserved for the exported todoʼs
+    if config.output.is_some() {
+        let found_count     = found_count.load(Ordering::Acquire);
+        let processed_count = processed_count.load(Ordering::Acquire);
+
+        config.mode.print_finish_msg(found_count, processed_count);
+    }
+
+    ExitCode::SUCCESS
+}
+
+async fn checking(
+    fm: Arc<FileManager>,
+    config: Arc<Config>,
+    found_count: Arc<AtomicUsize>,
+    processed_count: Arc<AtomicUsize>,
+) -> ExitCode {
+    // stalkr workers -> checker
+    let (checker_tx, checker_rx) = unbounded_channel();
+
+    let baseline = if config.use_baseline && !config.update_baseline {
+        match Baseline::load(&config.cwd) {
+            Ok(baseline) => baseline,
+            Err(e) => {
+                eprintln!("[{e:#}]");
+                return ExitCode::FAILURE
+            }
+        }
+    } else {
+        Baseline::default()
+    };
+
+    let mut checker = Checker::new(
+        fm.clone(),
+        config.clone(),
+        processed_count.clone(),
+        baseline
+    );
+
+    let stalkr_task = Stalkr::spawn(
+        fm,
+        config.clone(),
+        StalkrTx::Listing(checker_tx),
+        found_count.clone()
+    );
+
+    let (stalkr_res, violations) = tokio::join!(stalkr_task, checker.run(checker_rx));
+    stalkr_res.expect("[could not await parsing workers]");
+
+    let violations = match violations {
+        Ok(violations) => violations,
+        Err(e) => {
+            eprintln!("[{e:#}]");
+            return ExitCode::FAILURE
+        }
+    };
+
+    let found_count     = found_count.load(Ordering::Acquire);
+    let processed_count = processed_count.load(Ordering::Acquire);
+
+    config.mode.print_finish_msg(found_count, processed_count);
+
+    if violations == 0 {
+        ExitCode::SUCCESS
+    } else {
+        println!("[{violations} policy violation(s)]");
+        ExitCode::FAILURE
+    }
+}
+
+async fn closing(
+    fm: Arc<FileManager>,
+    config: Arc<Config>,
+    found_count: Arc<AtomicUsize>,
+    processed_count: Arc<AtomicUsize>,
+    max_http_concurrency: usize
+) -> ExitCode {
+    // stalkr workers -> tag collector
+    let (listing_tx, mut listing_rx) = unbounded_channel();
+
+    let stalkr_task = Stalkr::spawn(
+        fm.clone(),
+        config.clone(),
+        StalkrTx::Listing(listing_tx),
+        found_count.clone()
+    );
+
+    let mut tagged = HashSet::with_hasher(FxBuildHasher);
+
+    while let Some(prompt) = listing_rx.recv().await {
+        if let ModeValue::Listing(todos) = prompt.mode_value {
+            tagged.extend(todos.iter().filter_map(|todo| todo.issue_number));
+        }
+    }
+
+    stalkr_task.await.expect("[could not await parsing workers]");
+
+    let issues = match config.api.list_open_issues(&config).await {
+        Ok(issues) => issues,
+        Err(e) => {
+            eprintln!("[couldn't fetch open issues: {e:#}]");
+            return ExitCode::FAILURE
+        }
+    };
+
+    let orphans = close::find_orphans(&config, &fm, &tagged, issues);
+
+    found_count.store(orphans.len(), Ordering::Release);
+
+    if !orphans.is_empty() {
+        // prompter thread -> issue workers
+        let (issue_tx, issue_rx) = unbounded_channel();
+
+        // main -> prompter thread
+        let (prompter_tx, prompter_rx) = unbounded_channel();
+
+        let prompter_task = Prompter::spawn(
+            fm.clone(),
+            config.clone(),
+            PrompterTx::Issuer(issue_tx),
+            processed_count.clone(),
+            prompter_rx
+        );
+
+        let issue_task = Issuer::spawn(
+            IssuerTx::None,
+            config.clone(),
+            fm,
+            found_count.clone(),
+            processed_count.clone(),
+            max_http_concurrency,
+            issue_rx
+        );
+
+        _ = prompter_tx.send(Prompt { mode_value: ModeValue::Closing(orphans) });
+        drop(prompter_tx);
+
+        let (prompter_res, issue_res) = tokio::join!(prompter_task, issue_task);
+        prompter_res.expect("[could not await prompting thread]");
+        issue_res.expect("[could not await issuing workers]");
+    }
+
+    let found_count     = found_count.load(Ordering::Acquire);
+    let processed_count = processed_count.load(Ordering::Acquire);
+
+    config.mode.print_finish_msg(found_count, processed_count);
+
+    if config.http.is_aborted() {
+        eprintln!("[aborted: the issue tracker kept failing, the rest was left untouched]");
+        return ExitCode::FAILURE
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn managing_local_issue(cli: &Cli, action: &IssueAction) -> ExitCode {
+    let api = LocalApi::new(&cli.directory);
+
+    let (issue_number, state) = match action {
+        IssueAction::Close  { number } => (*number, State::Closed),
+        IssueAction::Reopen { number } => (*number, State::Open),
+    };
+
+    if let Err(e) = api.set_state(issue_number, state) {
+        eprintln!("[{e:#}]");
+        return ExitCode::FAILURE
+    }
+
+    println!("[issue #{issue_number} is {state}]", state = state.as_str());
+
+    ExitCode::SUCCESS
+}
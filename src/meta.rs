@@ -0,0 +1,136 @@
+use crate::util;
+
+use std::fmt;
+use std::sync::OnceLock;
+
+use serde_json::Value;
+
+/// Inline metadata of a TODO, e.g. `TODO(@alice, bug, P1):` or `TODO[label=perf]:`.
+///
+/// Items are comma-separated:
+///   `@name` or `assignee=name` -> assignee,
+///   `milestone=name`           -> milestone,
+///   `label=name` or `name`     -> label
+///
+/// In `(...)` there has to be an `@name` or a `key=value` item, `TODO(alice):` is a tag.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Meta {
+    pub labels: Vec<Box<str>>,
+    pub assignees: Vec<Box<str>>,
+    pub milestone: Option<Box<str>>
+}
+
+impl Meta {
+    #[inline(always)]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty() && self.assignees.is_empty() && self.milestone.is_none()
+    }
+
+    /// Returns `None` if there's an item with an unknown key, e.g. `foo=bar`
+    #[must_use]
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut meta = Self::default();
+
+        let boxed = |s: &str| util::string_into_boxed_str_norealloc(s.to_owned());
+
+        for item in s.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            if let Some(name) = item.strip_prefix('@') {
+                meta.assignees.push(boxed(name));
+                continue
+            }
+
+            let Some((key, value)) = item.split_once('=') else {
+                meta.labels.push(boxed(item));
+                continue
+            };
+
+            let value = value.trim();
+            if value.is_empty() { return None }
+
+            match key.trim() {
+                "label" | "labels" => meta.labels.push(boxed(value)),
+                "assignee" | "assignees" => {
+                    meta.assignees.push(boxed(value.strip_prefix('@').unwrap_or(value)));
+                }
+                "milestone" => meta.milestone = Some(boxed(value)),
+                _ => return None
+            }
+        }
+
+        Some(meta)
+    }
+
+    /// Items of `other` go after ours, its milestone wins
+    #[inline]
+    pub fn merge(&mut self, other: Self) {
+        self.labels.extend(other.labels);
+        self.assignees.extend(other.assignees);
+        if other.milestone.is_some() {
+            self.milestone = other.milestone;
+        }
+    }
+}
+
+/// `[@alice, bug, milestone=v1]`, the form the metadata is kept in next to a tag
+impl fmt::Display for Meta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let assignees = self.assignees.iter().map(|a| format!("@{a}"));
+        let labels = self.labels.iter().map(ToString::to_string);
+        let milestone = self.milestone.iter().map(|m| format!("milestone={m}"));
+
+        let items = assignees.chain(labels).chain(milestone).collect::<Vec<_>>();
+
+        write!(f, "[{items}]", items = items.join(", "))
+    }
+}
+
+/// Milestone ids by title, fetched once per run.
+/// Trackers take the id of a milestone when creating an issue, not its title.
+#[derive(Debug, Default)]
+pub struct Milestones(OnceLock<Vec<(Box<str>, u64)>>);
+
+impl Milestones {
+    /// Replaces the milestone title under `key` of an issue payload with its id,
+    /// dropping it if the milestone doesn't exist. `fetch` is only awaited until they are cached.
+    pub async fn resolve_in<F>(&self, body: &mut Value, key: &str, fetch: F)
+    where
+        F: Future<Output = anyhow::Result<Vec<(Box<str>, u64)>>>
+    {
+        let Some(obj) = body.as_object_mut() else { return };
+
+        let Some(title) = obj.remove("milestone") else { return };
+        let Some(title) = title.as_str() else { return };
+
+        let milestones = match self.0.get() {
+            Some(milestones) => milestones,
+            None => match fetch.await {
+                Ok(milestones) => self.0.get_or_init(|| milestones),
+                Err(e) => {
+                    eprintln!("[couldn't fetch milestones, leaving out {title:?}: {e:#}]");
+                    return
+                }
+            }
+        };
+
+        let id = milestones
+            .iter()
+            .find(|(t, _)| &**t == title)
+            .map(|(_, id)| *id)
+            .or_else(|| title.parse().ok());
+
+        match id {
+            Some(id) => _ = obj.insert(key.to_owned(), id.into()),
+            None => eprintln!("[no milestone named {title:?}, leaving it out]")
+        }
+    }
+
+    /// `(title, id)` of every milestone in a page of the tracker's milestones list
+    #[must_use]
+    pub fn from_json(milestones: &[Value], id_key: &str) -> Vec<(Box<str>, u64)> {
+        milestones.iter().filter_map(|m| Some((
+            m.get("title")?.as_str()?.into(),
+            m.get(id_key)?.as_u64()?
+        ))).collect()
+    }
+}
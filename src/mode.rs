@@ -1,4 +1,5 @@
 use crate::todo::Todo;
+use crate::close::Orphan;
 use crate::fm::FileId;
 use crate::purge::{Purge, Purges};
 
@@ -8,6 +9,8 @@ use std::hint;
 pub enum Mode {
     Purging,
     Listing,
+    Checking,
+    Closing,
     Reporting
 }
 
@@ -19,6 +22,8 @@ impl Mode {
             Self::Purging   => "purged",
             Self::Reporting => "reported",
             Self::Listing   => "listed",
+            Self::Checking  => "checked",
+            Self::Closing   => "closed",
         }
     }
 
@@ -29,6 +34,8 @@ impl Mode {
             Self::Purging   => "purge",
             Self::Reporting => "report",
             Self::Listing   => "list",
+            Self::Checking  => "check",
+            Self::Closing   => "close",
         }
     }
 
@@ -39,9 +46,18 @@ impl Mode {
             Self::Purging   => "purging",
             Self::Reporting => "reporting",
             Self::Listing   => "listing",
+            Self::Checking  => "checking",
+            Self::Closing   => "closing",
         }
     }
 
+    /// Modes that never talk to an issue tracker
+    #[must_use]
+    #[inline(always)]
+    pub const fn is_offline(&self) -> bool {
+        matches!(self, Self::Listing | Self::Checking)
+    }
+
     pub fn print_finish_msg(
         &self,
         found: usize,
@@ -61,7 +77,11 @@ impl Mode {
 pub enum ModeValue {
     Reporting(Vec<Todo>),
     Purging(Purges),
-    Listing(Vec<Todo>)
+    Listing(Vec<Todo>),
+    Checking(Vec<Todo>),
+
+    /// Issues to close, the todoʼs themselves are only listed when closing
+    Closing(Vec<Orphan>)
 }
 
 impl ModeValue {
@@ -79,7 +99,12 @@ impl ModeValue {
                 Vec::with_capacity(Self::RESERVE_CAP)
             ),
 
-            Mode::Listing => Self::Listing(
+            // closing only needs to know which tags are still in the tree
+            Mode::Listing | Mode::Closing => Self::Listing(
+                Vec::with_capacity(Self::RESERVE_CAP)
+            ),
+
+            Mode::Checking => Self::Checking(
                 Vec::with_capacity(Self::RESERVE_CAP)
             ),
         }
@@ -91,7 +116,9 @@ impl ModeValue {
         match self {
             Self::Purging(v)   => v.is_empty(),
             Self::Reporting(v) => v.is_empty(),
-            Self::Listing(v) => v.is_empty(),
+            Self::Listing(v)   => v.is_empty(),
+            Self::Checking(v)  => v.is_empty(),
+            Self::Closing(v)   => v.is_empty(),
         }
     }
 
@@ -100,7 +127,9 @@ impl ModeValue {
     pub fn push_purge(&mut self, purge: Purge) {
         match self {
             Self::Purging(ps) => ps.push(purge),
-            Self::Reporting(_) | Self::Listing(_) => unsafe { hint::unreachable_unchecked() }
+            Self::Reporting(_) | Self::Listing(_) | Self::Checking(_) | Self::Closing(_) => unsafe {
+                hint::unreachable_unchecked()
+            }
         }
     }
 
@@ -108,8 +137,8 @@ impl ModeValue {
     #[inline(always)]
     pub fn push_todo(&mut self, todo: Todo) {
         match self {
-            Self::Reporting(todos) | Self::Listing(todos) => todos.push(todo),
-            Self::Purging(_) => unsafe { hint::unreachable_unchecked() }
+            Self::Reporting(todos) | Self::Listing(todos) | Self::Checking(todos) => todos.push(todo),
+            Self::Purging(_) | Self::Closing(_) => unsafe { hint::unreachable_unchecked() }
         }
     }
 }
@@ -1,11 +1,13 @@
 use crate::util;
+use crate::index;
 use crate::loc::Loc;
 use crate::purge::Purges;
 use crate::config::Config;
 use crate::fm::FileManager;
 use crate::mode::ModeValue;
-use crate::todo::Description;
+use crate::todo::{Todo, Description};
 use crate::issue::IssueValue;
+use crate::template::IssueText;
 use crate::tag::InserterValue;
 
 use std::sync::Arc;
@@ -89,6 +91,11 @@ impl Prompter {
         let selection_string = Self::get_selection_string();
 
         while let Some(prompt) = prompter_rx.recv().await {
+            // the tracker is unreachable, don't ask about todoʼs we can't do anything with
+            if self.config.http.is_aborted() {
+                continue
+            }
+
             match prompt.mode_value {
                 ModeValue::Reporting(mut todos) => {
                     let Some(file_id) = todos.first().map(|t| t.loc.file_id()) else {
@@ -99,14 +106,22 @@ impl Prompter {
                         file_id
                     ).to_owned();
 
+                    let locations = todos.iter().map(|todo| {
+                        index::make_location(&self.config.cwd, &file_name, todo.loc.line_number())
+                    }).collect::<Vec<_>>();
+
                     let to_report = loop {
                         util::clear_screen();
 
                         self.print_header(&project_url, &file_name);
 
+                        // recomputed every time, as titles may have been edited
+                        let existing = self.find_existing_issues(&todos, &locations);
+
                         self.print_todos_with_descriptions(
                             &todos,
-                            |todo| &todo.loc,
+                            &existing,
+                            |todo| Self::line_label(todo.loc),
                             |todo| &todo.title,
                             |todo| todo.description.as_ref()
                         );
@@ -216,7 +231,8 @@ impl Prompter {
 
                     self.print_todos_with_descriptions(
                         &todos,
-                        |todo| &todo.loc,
+                        &[],
+                        |todo| Self::line_label(todo.loc),
                         |todo| &todo.title,
                         |todo| todo.description.as_ref()
                     );
@@ -227,6 +243,63 @@ impl Prompter {
                     Self::print_enter_to("move onto the next file");
                 }
 
+                ModeValue::Checking(_) => unreachable!("prompter never receives todoʼs to check"),
+
+                ModeValue::Closing(mut orphans) => {
+                    let to_close = loop {
+                        util::clear_screen();
+
+                        self.print_header(&project_url, "open issues whose todoʼs are gone");
+
+                        self.print_todos_with_descriptions(
+                            &orphans,
+                            &[],
+                            |orphan| format!("#{}", orphan.issue.number),
+                            |orphan| &orphan.issue.title,
+                            |orphan| Some(&orphan.description)
+                        );
+
+                        println!();
+
+                        let cmd = util::ask_input(&selection_string);
+                        let cmd = cmd.trim();
+
+                        if cmd.eq_ignore_ascii_case(Self::SKIP_KEY) { break None }
+                        if cmd.eq_ignore_ascii_case(Self::HELP_KEY) { Self::print_help(); continue }
+                        if cmd.eq_ignore_ascii_case(Self::ALL_KEY)  { break Some(orphans) }
+
+                        let mut close_indexes = Self::get_indexes_from_comma_separated(
+                            cmd,
+                            orphans.len()
+                        );
+
+                        if close_indexes.is_empty() { break None }
+
+                        close_indexes.sort_unstable();
+                        close_indexes.dedup();
+
+                        let mut selected = close_indexes
+                            .into_iter()
+                            .rev()
+                            .map(|i| orphans.remove(i))
+                            .collect::<Vec<_>>();
+
+                        selected.reverse(); // restore original order
+
+                        break Some(selected)
+                    };
+
+                    if let Some(to_close) = to_close {
+                        if self.tx
+                            .as_issuer_unchecked()
+                            .send(ModeValue::Closing(to_close))
+                            .is_err()
+                        {
+                            eprintln!("[could not send issues to issue worker]");
+                        }
+                    }
+                }
+
                 ModeValue::Purging(mut purges) => {
                     let Some(file_id) = purges.first().map(|p| p.tag.todo.loc.file_id()) else {
                         continue
@@ -243,7 +316,8 @@ impl Prompter {
 
                         self.print_todos_with_descriptions(
                             &purges,
-                            |purge| &purge.tag.todo.loc,
+                            &[],
+                            |purge| Self::line_label(purge.tag.todo.loc),
                             |purge| &purge.tag.todo.title,
                             |purge| purge.tag.todo.description.as_ref()
                         );
@@ -309,15 +383,18 @@ impl Prompter {
         };
     }
 
-    fn print_todos_with_descriptions<T, FLoc, FTitle, FDesc>(
+    /// `existing` are the numbers of already filed issues, by index of `items` (may be empty).
+    /// `get_label` is what goes in the brackets, e.g. `line 12`
+    fn print_todos_with_descriptions<T, FLabel, FTitle, FDesc>(
         &mut self,
         items: &[T],
-        get_loc: FLoc,
+        existing: &[Option<u64>],
+        get_label: FLabel,
         get_title: FTitle,
         get_description: FDesc,
     )
     where
-        FLoc   : Fn(&T) -> &Loc,
+        FLabel : Fn(&T) -> String,
         FTitle : Fn(&T) -> &str,
         FDesc  : Fn(&T) -> Option<&Description>,
     {
@@ -326,14 +403,14 @@ impl Prompter {
             .enumerate()
             .map(|(i, item)| {
                 let n_len = (i + 1).to_string().len();
-                let line_len = get_loc(item).line_number().to_string().len();
-                n_len + 2 + 6 + line_len + 2 // "N. [line X]:"
+                let label_len = get_label(item).len();
+                n_len + 2 + 1 + label_len + 2 // "N. [label]:"
             }).max().unwrap_or(0);
 
         for (i, item) in items.iter().enumerate() {
             self.stdout_buf.clear();
 
-            write_buf!(self, "{}. [line {}]:", i + 1, get_loc(item).line_number()).unwrap();
+            write_buf!(self, "{}. [{}]:", i + 1, get_label(item)).unwrap();
 
             let pad = max_width.saturating_sub(self.stdout_buf.len()).min(1);
             print!("{}", self.stdout_buf);
@@ -342,12 +419,39 @@ impl Prompter {
             }
             println!("{}", get_title(item));
 
+            if let Some(Some(issue_number)) = existing.get(i) {
+                println!("   └── already exists as #{issue_number}, selecting it reuses the issue");
+            }
+
             if let Some(desc) = get_description(item) {
                 println!("   └── description:\n{}", desc.display(9));
             }
         }
     }
 
+    #[inline]
+    fn line_label(loc: Loc) -> String {
+        format!("line {}", loc.line_number())
+    }
+
+    fn find_existing_issues(&self, todos: &[Todo], locations: &[String]) -> Vec<Option<u64>> {
+        let Some(index) = self.config.issue_index.get() else {
+            return Vec::new()
+        };
+
+        // the issue was filed with the rendered title
+        let titles = todos.iter().zip(locations).map(|(todo, location)| {
+            IssueText::render_title(&self.config, &self.fm, todo, location)
+        }).collect::<Vec<_>>();
+
+        let todos = titles.iter()
+            .zip(locations)
+            .map(|(title, location)| (title.as_str(), location.as_str()))
+            .collect::<Vec<_>>();
+
+        index.resolve(&todos)
+    }
+
     #[inline]
     fn get_selection_string() -> String {
         format!{
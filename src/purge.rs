@@ -1,11 +1,15 @@
+use crate::util;
 use crate::tag::Tag;
+use crate::hunk::{Hunk, LineMaps};
 use crate::config::Config;
-use crate::fm::{FileId, FileManager};
+use crate::fm::{self, FileId, FileManager};
 
-use std::fs::OpenOptions;
+use std::path::Path;
 use std::ops::{Range, Deref, DerefMut};
 use std::sync::atomic::{Ordering, AtomicUsize};
 
+use anyhow::Context;
+
 pub struct Purge {
     pub tag: Tag,
     pub range: Range<usize>
@@ -16,7 +20,8 @@ impl Purge {
     #[must_use] 
     pub fn commit_msg(&self) -> String {
         format!{
-            "Remove closed TODO{tag}: {title}",
+            "Remove closed {keyword}{tag}: {title}",
+            keyword = self.tag.todo.keyword,
             tag = self.tag,
             title = self.tag.todo.title
         }
@@ -57,55 +62,79 @@ impl Purges {
             return Ok(())
         }
 
-        self.purges.sort_by_key(|p| p.range.start);
+        let mut ranges = self.purges.iter().map(|p| p.range.clone()).collect::<Vec<_>>();
+
+        let mut buf = fm.read_for_edit(self.file_id, &mut ranges)?;
 
-        let mut new_len = fm.get_file_unchecked(self.file_id).meta.len() as usize;
+        // to undo the edit if the per-run commit can't be made
+        let original = config.git_locker.keeps_originals().then(|| buf.clone());
+
+        for (purge, range) in self.purges.iter_mut().zip(ranges) {
+            purge.range = range;
+        }
+
+        self.purges.sort_by_key(|p| p.range.start);
 
         let file_path = fm.get_file_path_unchecked(self.file_id).to_owned();
-        let mut mmap = fm.get_mmap_or_remmap_file_mut(self.file_id, new_len)?;
 
-        let truncate_file = |new_len: usize| -> anyhow::Result<()> {
-            OpenOptions::new()
-                .write(true)
-                .open(&file_path)?
-                .set_len(new_len as _)
-                .map_err(Into::into)
-        };
+        let git_locker = &config.git_locker;
 
-        for ref purge @ Purge { ref range, .. } in self.purges.into_iter().rev() {
-            let start = range.start;
-            let end   = range.end;
+        // every commit needs the file as it is after its purge, otherwise it's written once at the end
+        let writes_every_tag = git_locker.commits_every_tag();
+
+        let mut msgs = Vec::with_capacity(self.purges.len());
 
+        // only kept if they are going to be staged instead of the whole file
+        let needs_hunks = git_locker.needs_hunks(&file_path);
+        let mut hunks = Vec::new();
+
+        let line_maps = if needs_hunks { git_locker.line_maps(&file_path)? } else { LineMaps::default() };
+
+        // bottom to top, so that the ranges above stay where they are
+        for ref purge @ Purge { ref range, .. } in self.purges.into_iter().rev() {
             debug_assert!{
-                end <= new_len,
-                "purge range {range:?} past end {new_len}"
+                range.end <= buf.len(),
+                "purge range {range:?} past end {len}",
+                len = buf.len()
             };
 
-            let len = end - start;
-
-            // how many bytes follow this hole right now?
-            let tail_len = new_len - end;
+            let hunk = if needs_hunks {
+                let hunk = Hunk::new(&buf, range.clone(), &[], &line_maps);
+                Some(hunk.with_context(|| format!{
+                    "the lines of a TODO in {file_path} are part of your uncommitted changes, \
+                     so stalkr's change of them can't be staged on its own"
+                })?)
+            } else {
+                None
+            };
 
-            // reduce the effective length
-            new_len -= len;
+            buf.drain(range.clone());
 
-            // slide the tail block down on top of the hole
-            mmap.copy_within(end..end + tail_len, start);
+            let msg = purge.commit_msg();
 
-            mmap.flush()?; // Still good for safety
+            if writes_every_tag {
+                fm::write_atomically(Path::new(&file_path), &buf)?;
+                git_locker.commit_tag(&file_path, &msg, hunk.as_slice())?;
+                processed_count.fetch_add(1, Ordering::SeqCst);
+            }
 
-            truncate_file(new_len)?;
+            msgs.push(msg);
+            hunks.extend(hunk);
 
-            let msg = purge.commit_msg();
-            config.git_locker.commit_changes(&file_path, &msg)?;
+            config.tagged_issues.lock().unwrap().push((purge.tag.issue_number, purge.tag.todo.title.clone()));
+        }
 
-            processed_count.fetch_add(1, Ordering::SeqCst);
+        if !writes_every_tag {
+            fm::write_atomically(Path::new(&file_path), &buf)?;
+            processed_count.fetch_add(msgs.len(), Ordering::SeqCst);
         }
 
-        drop(mmap);
+        // purged bottom to top, listed top to bottom
+        msgs.reverse();
 
-        truncate_file(new_len)?;
+        let name = util::relative_path(&config.cwd, &file_path);
+        let subject = format!("Remove {n} closed TODOs from {name}", n = msgs.len());
 
-        Ok(())
+        config.git_locker.commit_file(&file_path, &name, &subject, msgs, hunks, original)
     }
 }
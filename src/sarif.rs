@@ -0,0 +1,88 @@
+use crate::util;
+use crate::export::Record;
+use crate::keyword::Keywords;
+
+use serde_json::{json, Value};
+
+const SCHEMA_URI: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const VERSION: &str = "2.1.0";
+
+// severity of TODO's that are not linked to an issue yet
+const UNTAGGED_LEVEL: &str = "warning";
+
+// severity of TODO's that are already tracked by an issue
+const TAGGED_LEVEL: &str = "note";
+
+/// Builds a SARIF 2.1.0 log out of listed TODO's: one rule per keyword, one result per TODO.
+#[must_use]
+pub fn make_log(records: &[Record], keywords: &Keywords) -> Value {
+    let rules = keywords.iter().map(|k| {
+        let labels = k.labels.iter().map(|l| Value::from(&**l)).collect::<Vec<_>>();
+        json!({
+            "id": k.name,
+            "name": k.name,
+            "shortDescription": { "text": format!("{k} comment") },
+            "defaultConfiguration": { "level": UNTAGGED_LEVEL },
+            "properties": { "tags": labels }
+        })
+    }).collect::<Vec<_>>();
+
+    let rule_index = |keyword: &str| keywords.iter().position(|k| &*k.name == keyword);
+
+    let results = records.iter().map(|r| {
+        let mut text = r.title.to_string();
+        for line in &r.description {
+            text.push('\n');
+            text.push_str(line);
+        }
+
+        let mut result = json!({
+            "ruleId": r.keyword,
+            "level": if r.is_tagged { TAGGED_LEVEL } else { UNTAGGED_LEVEL },
+            "message": { "text": text },
+            "locations": [{
+                "physicalLocation": {
+                    "artifactLocation": {
+                        "uri": encode_uri_path(&r.path),
+                        "uriBaseId": "%SRCROOT%"
+                    },
+                    "region": { "startLine": r.line }
+                }
+            }],
+            "properties": { "tagged": r.is_tagged }
+        });
+
+        if let Some(issue_number) = r.issue_number {
+            result["properties"]["issueNumber"] = issue_number.into();
+        }
+
+        if let Some(index) = rule_index(&r.keyword) {
+            result["ruleIndex"] = index.into();
+        }
+
+        result
+    }).collect::<Vec<_>>();
+
+    json!({
+        "$schema": SCHEMA_URI,
+        "version": VERSION,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": rules
+                }
+            },
+            "columnKind": "utf16CodeUnits",
+            "results": results
+        }]
+    })
+}
+
+// artifact locations are URI references, so escape whatever isn't allowed in a path
+#[inline]
+fn encode_uri_path(path: &str) -> String {
+    util::percent_encode(path, b"/-._~!$&'()*+,;=:@")
+}
@@ -0,0 +1,271 @@
+use crate::util;
+use crate::api::Backend;
+use crate::git::CommitStrategy;
+use crate::check::Policy;
+use crate::template::Template;
+use crate::keyword::{Keyword, Keywords};
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use serde_json::Value;
+
+/// Project-level settings, read from `.stalkr.json` in the scanned directory.
+///
+/// ```json
+/// {
+///     "keywords": [
+///         "TODO",
+///         { "name": "FIXME", "labels": ["bug"], "title_prefix": "[FIXME] " }
+///     ],
+///     "check": {
+///         "deny_untagged": true,
+///         "deny_malformed": true,
+///         "budgets": { "src/legacy": 40, ".": 200 }
+///     },
+///     "issues": {
+///         "labels": ["from-todo"],
+///         "keep_metadata": false,
+///         "title": "{{title}}",
+///         "body": "{{description}}\n\n{{permalink}} by {{author}}",
+///         "context_lines": 3
+///     },
+///     "commit": "per-file",
+///     "backend": "gitea",
+///     "github": { "url": "https://git.corp.example", "api_url": "https://git.corp.example/api/v3" },
+///     "gitlab": { "url": "https://gitlab.example.com" },
+///     "gitea": { "url": "https://codeberg.org", "token_env": "CODEBERG_TOKEN" }
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct Settings {
+    pub keywords: Keywords,
+    pub check: Policy,
+    pub issues: IssueSettings,
+
+    /// When edits get committed, see [`CommitStrategy`]
+    pub commit: CommitStrategy,
+
+    /// Issue tracker to use instead of detecting it from the git remote
+    pub backend: Option<Backend>,
+
+    pub github: Tracker,
+    pub gitlab: Tracker,
+    pub gitea: Tracker
+}
+
+/// How TODO's turn into issues
+#[derive(Debug)]
+pub struct IssueSettings {
+    /// Added to every created issue, e.g. `from-todo`
+    pub labels: Box<[Box<str>]>,
+
+    /// Keep the inline metadata (`TODO(@alice, bug):`) next to the inserted tag,
+    /// as `TODO(#12)[@alice, bug]:`, instead of leaving just `TODO(#12):`
+    pub keep_metadata: bool,
+
+    pub title: Template,
+    pub body: Template,
+
+    /// How many lines before and after the TODO go into `{{context}}`
+    pub context_lines: u32
+}
+
+impl Default for IssueSettings {
+    fn default() -> Self {
+        Self {
+            labels: Box::new([]),
+            keep_metadata: false,
+            title: Template::parse(Template::DEFAULT_TITLE).expect("valid default title template"),
+            body: Template::parse(Template::DEFAULT_BODY).expect("valid default body template"),
+            context_lines: Self::DEFAULT_CONTEXT_LINES
+        }
+    }
+}
+
+impl IssueSettings {
+    pub const DEFAULT_CONTEXT_LINES: u32 = 3;
+
+    fn from_json(json: &Value) -> anyhow::Result<Self> {
+        let mut issues = Self::default();
+
+        if let Some(labels) = json.get("labels") {
+            issues.labels = util::json_str_array(labels)
+                .context("`issues.labels` must be an array of strings")?;
+        }
+
+        if let Some(keep_metadata) = json.get("keep_metadata") {
+            let Some(keep_metadata) = keep_metadata.as_bool() else {
+                bail!("`issues.keep_metadata` must be a boolean")
+            };
+
+            issues.keep_metadata = keep_metadata;
+        }
+
+        for (key, template) in [("title", &mut issues.title), ("body", &mut issues.body)] {
+            let Some(value) = json.get(key) else { continue };
+
+            let Some(value) = value.as_str() else {
+                bail!("`issues.{key}` must be a string")
+            };
+
+            *template = Template::parse(value).with_context(|| format!("invalid `issues.{key}`"))?;
+        }
+
+        if let Some(context_lines) = json.get("context_lines") {
+            let Some(context_lines) = context_lines.as_u64().and_then(|n| u32::try_from(n).ok()) else {
+                bail!("`issues.context_lines` must be a number")
+            };
+
+            issues.context_lines = context_lines;
+        }
+
+        Ok(issues)
+    }
+}
+
+/// Where a (possibly self-hosted) issue tracker lives
+#[derive(Debug, Default)]
+pub struct Tracker {
+    /// Base url of the instance, e.g. `https://gitlab.example.com`
+    pub url: Option<Box<str>>,
+
+    /// Base url of the REST api, if it isn't at `url` + `/api/v3` (GitHub only)
+    pub api_url: Option<Box<str>>,
+
+    /// Env variable to read the token from, instead of the backend's default one
+    pub token_env: Option<Box<str>>
+}
+
+impl Tracker {
+    fn from_json(json: &Value, name: &str) -> anyhow::Result<Self> {
+        let get_str = |key: &str| -> anyhow::Result<Option<Box<str>>> {
+            json.get(key).map(|v| {
+                v.as_str().map(Into::into).with_context(|| format!("`{name}.{key}` must be a string"))
+            }).transpose()
+        };
+
+        let url = get_str("url")?.map(|url| url.trim_end_matches('/').into());
+        let api_url = get_str("api_url")?.map(|url| url.trim_end_matches('/').into());
+        let token_env = get_str("token_env")?;
+
+        Ok(Self { url, api_url, token_env })
+    }
+}
+
+impl Settings {
+    pub const FILE_NAME: &str = ".stalkr.json";
+
+    pub fn load(dir: &Path) -> anyhow::Result<Self> {
+        let path = dir.join(Self::FILE_NAME);
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e).with_context(|| format!("couldn't read {}", path.display()))
+        };
+
+        let json = serde_json::from_str::<Value>(&contents)
+            .with_context(|| format!("couldn't parse {}", path.display()))?;
+
+        Self::from_json(&json).with_context(|| format!("invalid {}", path.display()))
+    }
+
+    fn from_json(json: &Value) -> anyhow::Result<Self> {
+        let mut settings = Self::default();
+
+        if let Some(keywords) = json.get("keywords") {
+            let Some(keywords) = keywords.as_array() else {
+                bail!("`keywords` must be an array")
+            };
+
+            let keywords = keywords
+                .iter()
+                .map(Self::parse_keyword)
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            if keywords.is_empty() {
+                bail!("`keywords` must not be empty")
+            }
+
+            settings.keywords = Keywords::new(keywords);
+        }
+
+        if let Some(check) = json.get("check") {
+            settings.check = Policy::from_json(check)?;
+        }
+
+        if let Some(issues) = json.get("issues") {
+            settings.issues = IssueSettings::from_json(issues)?;
+        }
+
+        if let Some(commit) = json.get("commit") {
+            let Some(commit) = commit.as_str().and_then(CommitStrategy::from_name) else {
+                bail!("`commit` must be one of: {names}", names = CommitStrategy::NAMES)
+            };
+
+            settings.commit = commit;
+        }
+
+        if let Some(backend) = json.get("backend") {
+            let Some(backend) = backend.as_str().and_then(Backend::from_name) else {
+                bail!("`backend` must be one of: github, gitlab, gitea, local")
+            };
+
+            settings.backend = Some(backend);
+        }
+
+        if let Some(github) = json.get("github") {
+            settings.github = Tracker::from_json(github, "github")?;
+        }
+
+        if let Some(gitlab) = json.get("gitlab") {
+            settings.gitlab = Tracker::from_json(gitlab, "gitlab")?;
+        }
+
+        if let Some(gitea) = json.get("gitea") {
+            settings.gitea = Tracker::from_json(gitea, "gitea")?;
+        }
+
+        Ok(settings)
+    }
+
+    fn parse_keyword(json: &Value) -> anyhow::Result<Keyword> {
+        let (name, obj) = match json {
+            Value::String(name) => (name.as_str(), None),
+            Value::Object(obj) => {
+                let Some(name) = obj.get("name").and_then(Value::as_str) else {
+                    bail!("keyword is missing a `name`")
+                };
+                (name, Some(obj))
+            }
+            _ => bail!("keyword must be either a string or an object")
+        };
+
+        if !Keyword::is_valid_name(name) {
+            bail!("invalid keyword name: {name:?}")
+        }
+
+        let mut keyword = Keyword::new(name);
+
+        let Some(obj) = obj else {
+            return Ok(keyword)
+        };
+
+        if let Some(labels) = obj.get("labels") {
+            keyword.labels = util::json_str_array(labels)
+                .with_context(|| format!("`labels` of {name} must be an array of strings"))?;
+        }
+
+        if let Some(prefix) = obj.get("title_prefix") {
+            let Some(prefix) = prefix.as_str() else {
+                bail!("`title_prefix` of {name} must be a string")
+            };
+
+            keyword.title_prefix = Some(util::string_into_boxed_str_norealloc(prefix.to_owned()));
+        }
+
+        Ok(keyword)
+    }
+}
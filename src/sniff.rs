@@ -0,0 +1,146 @@
+use crate::fm::FxDashMap;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+// how much of a file we look at to decide if it's binary or generated
+pub const SNIFF_LEN: usize = 8 * 1024;
+
+// generated-file markers are expected somewhere in the first lines of a file
+const GENERATED_HEADER_LINES: usize = 20;
+
+const GENERATED_MARKERS: &[&[u8]] = &[b"@generated", b"DO NOT EDIT"];
+
+/// Returns true if `prefix` (the first bytes of a file) looks like binary data:
+/// it either contains a NUL byte, or too much of it isn't valid UTF-8.
+#[must_use]
+pub fn is_binary(prefix: &[u8]) -> bool {
+    let prefix = &prefix[..prefix.len().min(SNIFF_LEN)];
+
+    if memchr::memchr(0, prefix).is_some() {
+        return true
+    }
+
+    let invalid = prefix
+        .utf8_chunks()
+        .map(|chunk| chunk.invalid().len())
+        .sum::<usize>();
+
+    // a multi-byte character may have been cut off at the end of the window
+    let invalid = invalid.saturating_sub(3);
+
+    // more than ~10% of invalid UTF-8
+    invalid * 10 > prefix.len()
+}
+
+/// Returns true if the header of a file (`prefix`) carries an `@generated` or `DO NOT EDIT` marker.
+#[must_use]
+pub fn has_generated_header(prefix: &[u8]) -> bool {
+    let prefix = &prefix[..prefix.len().min(SNIFF_LEN)];
+
+    prefix
+        .split(|&b| b == b'\n')
+        .take(GENERATED_HEADER_LINES)
+        .any(|line| {
+            GENERATED_MARKERS.iter().any(|m| memchr::memmem::find(line, m).is_some())
+        })
+}
+
+/// Matches files that `.gitattributes` mark as generated (`linguist-generated`)
+/// or not worth diffing (`-diff`, `binary`).
+///
+/// Every directory's `.gitattributes` is parsed once and cached.
+#[derive(Default)]
+pub struct GitAttributes {
+    // directory -> matcher built from the `.gitattributes` in it, if there's one
+    dirs: FxDashMap<PathBuf, Option<Gitignore>>
+}
+
+impl GitAttributes {
+    const FILE_NAME: &str = ".gitattributes";
+
+    /// Returns true if `path` is marked as generated by the closest `.gitattributes` that mentions it.
+    #[must_use]
+    pub fn is_generated(&self, path: &Path) -> bool {
+        let mut dir = path.parent();
+
+        while let Some(d) = dir {
+            if let Some(is_generated) = self.matched(d, path) {
+                return is_generated
+            }
+
+            // don't look above the repository
+            if d.join(".git").exists() { break }
+
+            dir = d.parent();
+        }
+
+        false
+    }
+
+    // Some(true) if set, Some(false) if explicitly unset, None if not mentioned
+    fn matched(&self, dir: &Path, path: &Path) -> Option<bool> {
+        if let Some(matcher) = self.dirs.get(dir) {
+            return Self::match_with(matcher.as_ref(), path)
+        }
+
+        let matcher = Self::load(dir);
+        let m = Self::match_with(matcher.as_ref(), path);
+        self.dirs.insert(dir.to_owned(), matcher);
+        m
+    }
+
+    #[inline]
+    fn match_with(matcher: Option<&Gitignore>, path: &Path) -> Option<bool> {
+        match matcher?.matched(path, false) {
+            Match::Ignore(_)    => Some(true),
+            Match::Whitelist(_) => Some(false),
+            Match::None         => None
+        }
+    }
+
+    // translates `.gitattributes` into a gitignore matcher, where "ignored" means generated
+    // and "whitelisted" means that a later line explicitly unset the attributes
+    fn load(dir: &Path) -> Option<Gitignore> {
+        let path = dir.join(Self::FILE_NAME);
+        let contents = fs::read_to_string(&path).ok()?;
+
+        let mut builder = GitignoreBuilder::new(dir);
+
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue }
+
+            let mut parts = line.split_whitespace();
+            let Some(pattern) = parts.next() else { continue };
+
+            let mut generated = None;
+            for attr in parts {
+                match attr {
+                    "linguist-generated" | "linguist-generated=true" | "-diff" | "binary" => {
+                        generated = Some(true);
+                    }
+                    "-linguist-generated" | "linguist-generated=false" | "diff" => {
+                        generated = Some(false);
+                    }
+                    _ => {}
+                }
+            }
+
+            let line = match generated {
+                Some(true)  => pattern.to_owned(),
+                Some(false) => format!("!{pattern}"),
+                None => continue
+            };
+
+            if let Err(e) = builder.add_line(Some(path.clone()), &line) {
+                eprintln!("[{p}: invalid pattern]: {e}", p = path.display());
+            }
+        }
+
+        builder.build().ok()
+    }
+}
@@ -1,10 +1,13 @@
 use crate::util;
+use crate::sniff::{self, GitAttributes};
 use crate::tag::Tag;
 use crate::loc::Loc;
 use crate::fm::FileId;
-use crate::todo::Todo;
+use crate::todo::{Todo, TodoHead};
 use crate::purge::Purge;
 use crate::config::Config;
+use crate::local::LocalApi;
+use crate::syntax::Syntax;
 use crate::comment::Comment;
 use crate::issue::IssueValue;
 use crate::mode::{Mode, ModeValue};
@@ -13,7 +16,8 @@ use crate::fm::{FileManager, StalkrFile};
 
 use std::str;
 use std::sync::Arc;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::ops::Range;
 use std::fs::OpenOptions;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
@@ -25,6 +29,9 @@ use tokio::sync::mpsc::UnboundedSender;
 #[allow(clippy::identity_op)]
 const MMAP_THRESHOLD: usize = 1 * 1024 * 1024;
 
+// gitignore-syntax file for stalkr-only exclusions
+pub const STALKR_IGNORE_FILE_NAME: &str = ".stalkrignore";
+
 pub enum StalkrTx {
     Issuer(UnboundedSender<IssueValue>),
     Prompter(UnboundedSender<Prompt>),
@@ -35,7 +42,8 @@ pub struct Stalkr {
     stalkr_tx: StalkrTx,
     config: Arc<Config>,
     fm: Arc<FileManager>,
-    found_count: Arc<AtomicUsize>
+    found_count: Arc<AtomicUsize>,
+    gitattributes: GitAttributes
 }
 
 impl Stalkr {
@@ -52,20 +60,45 @@ impl Stalkr {
 
     #[inline]
     pub fn run(&self) {
-        dir_rec::DirRec::new(&*self.config.cwd)
+        Self::walk(&self.config.cwd)
             .filter(|p| Stalkr::filter(p))
             .par_bridge()
             .for_each(|e| _ = self.stalk(e.as_path()));
     }
 
+    /// Recursively walks `dir`, honouring `.gitignore` (nested ones, negations,
+    /// `.git/info/exclude` and the global excludes file), `.ignore` and `.stalkrignore`.
+    pub fn walk(dir: &Path) -> impl Iterator<Item = PathBuf> + Send {
+        ignore::WalkBuilder::new(dir)
+            .hidden(false)
+            .require_git(false)
+            .add_custom_ignore_filename(STALKR_IGNORE_FILE_NAME)
+            // issues of the local tracker aren't source code
+            .filter_entry(|e| {
+                e.file_name() != ".git" && !e.path().ends_with(LocalApi::ISSUES_DIR)
+            })
+            .build()
+            .filter_map(|e| match e {
+                Ok(e) => Some(e),
+                Err(err) => {
+                    eprintln!("[could not walk directory entry]: {err}");
+                    None
+                }
+            })
+            .filter(|e| e.file_type().is_some_and(|t| t.is_file()))
+            .map(ignore::DirEntry::into_path)
+    }
+
     #[inline(always)]
-    pub const fn new(
+    #[must_use]
+    pub fn new(
         fm: Arc<FileManager>,
         config: Arc<Config>,
         stalkr_tx: StalkrTx,
         found_count: Arc<AtomicUsize>
     ) -> Self {
-        Self { stalkr_tx, config, fm, found_count }
+        let gitattributes = GitAttributes::default();
+        Self { stalkr_tx, config, fm, found_count, gitattributes }
     }
 
     pub fn stalk(&self, file_path: &Path) -> anyhow::Result<()> {
@@ -73,9 +106,20 @@ impl Stalkr {
             return Ok(())
         }
 
+        if self.gitattributes.is_generated(file_path) {
+            return Ok(())
+        }
+
+        let edits_files = match self.config.mode {
+            Mode::Reporting => !self.config.simulate_reporting,
+            Mode::Purging   => true,
+            Mode::Listing | Mode::Checking | Mode::Closing => false
+        };
+
+        // listing and checking never write, so they work on read-only checkouts too
         let file = OpenOptions::new()
             .read(true)
-            .write(true)
+            .write(edits_files)
             .open(file_path)?;
 
         let meta = file.metadata()?;
@@ -92,18 +136,55 @@ impl Stalkr {
 
         let file_id = self.fm.next_file_id();
 
-        let mode_value = if file_size < MMAP_THRESHOLD {
+        let syntax = Syntax::from_path(file_path);
+
+        // don't bother with binary and generated files
+        let should_skip = |haystack: &[u8]| {
+            sniff::is_binary(haystack) || sniff::has_generated_header(haystack)
+        };
+
+        // closing needs to know which files were searched, to tell removed TODO's apart from unseen ones
+        let mark_scanned = || if self.config.mode == Mode::Closing {
+            self.fm.mark_scanned(file_path);
+        };
+
+        // files that may be edited are read into a buffer whatever their size: a mapping
+        // would follow changes made to the file after the scan, and those have to be
+        // told apart from the scanned contents before editing, see [`FileManager::read_for_edit`]
+        let mode_value = if file_size < MMAP_THRESHOLD || edits_files {
             let buf = stalkr_file.read_file_to_vec()?;
-            self.search(buf, path_str, file_id)
+            if should_skip(buf) { return Ok(()) }
+            mark_scanned();
+            self.search(buf, path_str, file_id, syntax)
         } else {
             let mmap = stalkr_file.mmap_file()?;
-            self.search(&mmap[..], path_str, file_id)
+            if should_skip(&mmap[..]) { return Ok(()) }
+            mark_scanned();
+            self.search(&mmap[..], path_str, file_id, syntax)
         };
 
         if mode_value.is_empty() {
             return Ok(())
         }
 
+        // before any issue is filed for it, as its tags couldn't be inserted
+        if edits_files && self.config.git_locker.should_skip(path_str) {
+            eprintln!{
+                "[skipping {path_str}: it has uncommitted changes, commit or stash them, \
+                 or pass --allow-dirty or --stage-own-changes]"
+            };
+
+            if let ModeValue::Reporting(todos) = &mode_value {
+                self.found_count.fetch_sub(todos.len(), Ordering::SeqCst);
+            }
+
+            return Ok(())
+        }
+
+        if edits_files {
+            stalkr_file.record_hash();
+        }
+
         self.fm.register_stalkr_file(stalkr_file, file_id);
 
         match &self.stalkr_tx {
@@ -134,13 +215,21 @@ impl Stalkr {
         &self,
         haystack: &[u8],
         file_path: &str,
-        file_id: FileId
+        file_id: FileId,
+        syntax: &Syntax
     ) -> ModeValue {
         let mut mode_value = ModeValue::new(self.config.mode, file_id);
 
         let mut byte_offset = 0;
         let mut line_number = 1;
 
+        // block comment that is still open at the start of the current line
+        let mut open_block = None;
+
+        // (line start, opener position) of the previous line,
+        // if it was nothing but a block opener, e.g. `/**`
+        let mut bare_opener = None;
+
         while byte_offset < haystack.len() {
             // find next newline
             let nl_rel = memchr::memchr(b'\n', &haystack[byte_offset..]);
@@ -150,135 +239,146 @@ impl Stalkr {
             };
 
             let line = &haystack[byte_offset..line_end];
+            let line_start = byte_offset;
 
             byte_offset = line_end;
             line_number += 1;
 
+            let prev_bare_opener = bare_opener.take();
+
             let Ok(line_str) = str::from_utf8(line) else {
                 continue
             };
 
-            // find the first comment marker anywhere in the line
-            let mut comment_scan_index = 0;
-            let mut found_comment = None;
-            let mut comment = Comment::Slash;
+            let line_str_trimmed_len = line_str.trim_end().len();
 
-            while comment_scan_index < line.len() {
-                let rel = memchr::memchr3(
-                    Comment::Hash as _,
-                    Comment::Slash as _,
-                    Comment::Dash as _,
-                    &line[comment_scan_index..]
-                );
-
-                let Some(rel) = rel else { break };
-
-                let index = comment_scan_index + rel;
+            // `rel_comment_start` is `None` when the whole line lives inside of a block comment
+            let (
+                comment,
+                rel_comment_start,
+                rel_content_start,
+                rel_closer
+            ) = if let Some(block @ Comment::Block { close, .. }) = open_block {
+                let content_start = block.block_line_content_start(line_str);
+
+                let close_pos = line_str[content_start..]
+                    .find(close)
+                    .map(|p| content_start + p);
+
+                open_block = match close_pos {
+                    Some(p) => syntax.open_block_after(line, p + close.len()),
+                    None    => Some(block)
+                };
+
+                (block, None, content_start, close_pos)
+            } else {
+                // find the first comment marker anywhere in the line
+                let Some((comment_start, comment)) = syntax.find_comment_start(line) else {
+                    continue // no comment on this line
+                };
+
+                let content_start = comment_start + comment.opener().len();
+
+                let close_pos = comment.closer().and_then(|close| {
+                    line_str[content_start..].find(close).map(|p| content_start + p)
+                });
+
+                if let Comment::Block { close, .. } = comment {
+                    open_block = match close_pos {
+                        Some(p) => syntax.open_block_after(line, p + close.len()),
+                        None    => Some(comment)
+                    };
 
-                comment = Comment::from_u8_unchecked(line[index]);
+                    let is_bare_opener = close_pos.is_none()
+                        && line_str[..comment_start].trim().is_empty()
+                        && line_str[content_start..].trim().bytes().all(|b| matches!(b, b'*' | b'!'));
 
-                // safe to slice at `idx` because memchr found an ASCII byte
-                if let Some(marker_len) = util::is_line_a_comment(&line_str[index..]) {
-                    found_comment = Some((index, marker_len));
-                    break
+                    if is_bare_opener {
+                        bare_opener = Some((line_start, comment_start));
+                    }
                 }
 
-                comment_scan_index = index + 1; // continue searching after the found byte
-            }
-
-            let Some((rel_comment_start, marker_len)) = found_comment else {
-                continue // no comment on this line
+                (comment, Some(comment_start), content_start, close_pos)
             };
 
+            let content_end = rel_closer
+                .unwrap_or(line_str_trimmed_len)
+                .max(rel_content_start);
+
             // the part after the comment marker, BEFORE trimming spaces
-            let rest_after_mark = &line_str[rel_comment_start + marker_len..];
+            let rest_after_mark = &line_str[rel_content_start..content_end];
             let content = rest_after_mark.trim_start(); // content after marker and any spaces
 
             let ws_after_marker = rest_after_mark.len() - content.len();
 
-            // helper: find TODO inside a byte slice using memchr
-            fn position_todo(haystack: &[u8], start: usize) -> Option<usize> {
-                let mut pos = start;
-                while let Some(i) = memchr::memchr(b'T', &haystack[pos..]) {
-                    let index = pos + i;
-                    if haystack[index..].starts_with(b"TODO") {
-                        return Some(index)
-                    }
-                    pos = index + 1;
-                }
-
-                None
-            }
-
-            let content_bytes = content.as_bytes();
-            let Some(todo_idx_in_content) = position_todo(content_bytes, 0) else {
+            // we require the keyword to be right after the comment (after optional whitespace),
+            // i.e. at the start of `content`.
+            let Some(keyword) = self.config.settings.keywords.match_start(content) else {
                 continue
             };
 
-            // we require the TODO to be right after the comment (after optional whitespace),
-            // i.e. at the start of `content`.
-            if todo_idx_in_content != 0 {
-                // TODO is present but not immediately after the comment marker -> skip
+            let Some(head) = Todo::extract_todo_head(content, &keyword.name) else {
                 continue
-            }
-
-            // todo_slice starts at the "TODO" occurrence (within trimmed content)
-            let todo_slice = &content[todo_idx_in_content..];
+            };
 
-            let is_untagged = todo_slice.starts_with("TODO:");
-            let (title, is_tagged) = Todo::extract_todo_title(todo_slice);
+            if head.title.is_empty() { continue }
 
-            if title.trim().is_empty() { continue }
+            let TodoHead { title, is_tagged, issue_number, meta, groups_len } = head;
 
-            if !is_tagged && !is_untagged { continue }
+            let is_untagged = !is_tagged;
 
             let loc = Loc(file_id, line_number - 1);
 
-            let line_start = byte_offset - line.len();
-
             // position where to insert tag: compute absolute byte offset in file.
-            // line_start + comment_pos = start of comment marker in file
-            // + marker_len + ws_after_marker = start of `content` in file
-            // + todo_idx_in_content = start of TODO in content
-            // + "TODO".len() = position after the word TODO
-            let tag_insertion_offset = line_start
-                + rel_comment_start
-                + marker_len
-                + ws_after_marker
-                + todo_idx_in_content
-                + "TODO".len();
+            // line_start + rel_content_start = start of the comment's content in file
+            // + ws_after_marker = start of the keyword in file
+            // + keyword.name.len() = position after the keyword
+            let todo_start = line_start + rel_content_start + ws_after_marker;
+            let tag_insertion_offset = todo_start + keyword.name.len();
+
+            // a block comment is still open after this line, so the description lives in it
+            let is_in_open_block = rel_closer.is_none() && matches!(comment, Comment::Block { .. });
+
+            let (
+                description,
+                block_close
+            ) = if is_in_open_block {
+                Todo::extract_block_todo_description(
+                    &haystack[byte_offset..],
+                    comment,
+                    &self.config.settings.keywords
+                )
+            } else if rel_closer.is_none() {
+                (Todo::extract_todo_description(
+                    &haystack[byte_offset..],
+                    comment,
+                    &self.config.settings.keywords
+                ), None)
+            } else {
+                (None, None)
+            };
 
             let (
                 description,
                 description_line_end
-            ) = Todo::extract_todo_description(
-                &haystack[byte_offset..],
-                comment
-            ).map_or((None, None), |(d, l)| (Some(d), Some(l)));
+            ) = description.map_or((None, None), |(d, l)| (Some(d), Some(l)));
+
+            // file_id is not yet registered, so use file_path instead
+            let display_loc = || loc.display_from_str(file_path);
 
             let todo = Todo {
                 loc,
+                keyword: keyword.clone(),
                 description,
+                is_tagged,
+                issue_number,
                 tag_insertion_offset,
+                tag_replace_len: groups_len,
+                meta,
                 preview: util::string_into_boxed_str_norealloc(content.to_owned()),
                 title: util::string_into_boxed_str_norealloc(title.to_owned()),
             };
 
-            // file_id is not yet registered, so use file_path instead
-            let display_loc = || loc.display_from_str(file_path);
-
-            let try_get_issue_number = || {
-                let skip = "TODO(#".len();
-
-                let closing_paren_pos = content[skip..].find(')')?;
-
-                let issue_number = content[
-                    skip..skip + closing_paren_pos
-                ].parse::<u64>().ok()?;
-
-                Some(issue_number)
-            };
-
             match self.config.mode {
                 Mode::Reporting => if is_untagged {
                     self.found_count.fetch_add(1, Ordering::SeqCst);
@@ -286,7 +386,7 @@ impl Stalkr {
                 }
 
                 Mode::Purging => if is_tagged {
-                    let Some(issue_number) = try_get_issue_number() else {
+                    let Some(issue_number) = issue_number else {
                         eprintln!{
                             "[{loc}: error: failed to parse issue number]",
                             loc = display_loc()
@@ -295,67 +395,174 @@ impl Stalkr {
                         continue
                     };
 
-                    let mut comment_ws_pos = rel_comment_start;
-                    while comment_ws_pos > 0 && line[comment_ws_pos - 1] == b' ' {
-                        comment_ws_pos -= 1;
-                    }
-
-                    let global_comment_start = line_start + comment_ws_pos;
-
-                    let line_end = description_line_end.map_or(line_end, |dl| {
+                    let description_end = description_line_end.map_or(line_end, |dl| {
                         dl + byte_offset
                     });
 
-                    let global_comment_end = if rel_comment_start == 0 {
-                        // include newline in this line for the purge
-                        line_end
-                    } else {
-                        // don't include
-                        line_end.saturating_sub(1)
+                    // the closer of the block comment, if it was reached on this line or in the description
+                    let closer = match (rel_closer, block_close) {
+                        (Some(p), _) => Some((
+                            line_start + p,
+                            line_start + p + comment.closer().map_or(0, str::len),
+                            line_end
+                        )),
+                        (None, Some(bc)) => Some((
+                            bc.start + byte_offset,
+                            bc.end + byte_offset,
+                            bc.line_end + byte_offset
+                        )),
+                        (None, None) => None
                     };
 
+                    let range = Self::purge_range(
+                        haystack,
+                        comment,
+                        line_start..line_end,
+                        rel_comment_start,
+                        prev_bare_opener,
+                        todo_start,
+                        description_end,
+                        closer
+                    );
+
                     mode_value.push_purge(Purge {
                         tag: Tag { issue_number, todo },
-                        range: global_comment_start..global_comment_end
+                        range
                     });
                 }
 
-                Mode::Listing => {
+                Mode::Listing | Mode::Checking => {
                     self.found_count.fetch_add(1, Ordering::SeqCst);
                     mode_value.push_todo(todo);
                 }
+
+                // only the tags matter, it's the issues to close that are counted
+                Mode::Closing => if is_tagged {
+                    mode_value.push_todo(todo);
+                }
             }
         }
 
         mode_value
     }
 
+    /// Computes the range of bytes to remove when purging a TODO.
+    ///
+    /// `closer` is the (start, end, line end) of the block comment closer,
+    /// if the block closes on the TODO's line or right after its description.
+    #[allow(clippy::too_many_arguments)]
+    fn purge_range(
+        haystack: &[u8],
+        comment: Comment,
+        todo_line: Range<usize>,
+        rel_comment_start: Option<usize>,
+        prev_bare_opener: Option<(usize, usize)>,
+        todo_start: usize,
+        description_end: usize,
+        closer: Option<(usize, usize, usize)>
+    ) -> Range<usize> {
+        // walk back over the whitespace that precedes a comment marker
+        let ws_start = |line_start: usize, rel: usize| {
+            let mut rel = rel;
+            while rel > 0 && matches!(haystack[line_start + rel - 1], b' ' | b'\t') {
+                rel -= 1;
+            }
+            rel
+        };
+
+        let strip_newline = |end: usize| {
+            if end > 0 && haystack[end - 1] == b'\n' { end - 1 } else { end }
+        };
+
+        // remove `start..end` where `start` is at `rel` into the line starting at `line_start`,
+        // taking the whole lines if nothing but whitespace precedes the comment
+        let comment_range = |line_start: usize, rel: usize, end: usize| {
+            let rel = ws_start(line_start, rel);
+            if rel == 0 {
+                line_start..end
+            } else {
+                line_start + rel..strip_newline(end)
+            }
+        };
+
+        let Comment::Block { .. } = comment else {
+            let rel_comment_start = rel_comment_start.unwrap_or_default();
+            return comment_range(todo_line.start, rel_comment_start, description_end)
+        };
+
+        // the whole block consists of this TODO, so remove it entirely, including the closer
+        let opener = rel_comment_start
+            .map(|rel| (todo_line.start, rel))
+            .or(prev_bare_opener);
+
+        if let (Some((opener_line_start, rel_opener)), Some((_, close_end, close_line_end))) = (opener, closer) {
+            let is_closer_last = haystack[close_end..close_line_end]
+                .iter()
+                .all(u8::is_ascii_whitespace);
+
+            if is_closer_last {
+                return comment_range(opener_line_start, rel_opener, close_line_end)
+            }
+        }
+
+        // the block goes on after the TODO, keep the block and remove the TODO only
+        match (rel_comment_start, closer) {
+            // TODO is right after the opener and the block closes on the same line,
+            // e.g. `/* TODO(#1): .. */ code`
+            (Some(rel), Some((_, close_end, close_line_end))) if close_line_end == todo_line.end => {
+                todo_line.start + ws_start(todo_line.start, rel)..close_end
+            }
+
+            // keep the closer (and whatever follows it) in place
+            (_, Some((close_start, _, _))) if close_start < description_end => {
+                let start = if rel_comment_start.is_some() {
+                    todo_start
+                } else {
+                    let indent = haystack[todo_line.clone()]
+                        .iter()
+                        .take_while(|b| matches!(b, b' ' | b'\t'))
+                        .count();
+
+                    todo_line.start + indent
+                };
+
+                start..close_start
+            }
+
+            // TODO is right after the opener and the block goes on in the next lines
+            (Some(_), _) => todo_start..description_end,
+
+            // TODO is on its own line(s) inside of the block
+            (None, _) => todo_line.start..description_end
+        }
+    }
+
     #[inline]
     #[must_use]
     pub fn filter(e: &Path) -> bool {
+        // formats that are always binary, so there's no need to even open them.
+        // Anything that can be text (`.ts` is also MPEG transport stream, `.d` is also
+        // a make dependency file) is left to the sniffer.
         pub const BINARY_EXTENSIONS: phf::Set::<&[u8]> = phf::phf_set! {
             b"exe", b"dll", b"bin", b"o", b"so", b"a", b"lib", b"elf", b"class",
             b"jar", b"war", b"ear", b"apk", b"msi", b"iso", b"img", b"dmg", b"vmdk",
-            b"vhd", b"vdi", b"rom", b"efi", b"sys", b"ko", b"bz2", b"xz", b"7z",
+            b"vhd", b"vdi", b"rom", b"efi", b"ko", b"bz2", b"xz", b"7z",
             b"gz", b"zip", b"rar", b"tar", b"arj", b"lz", b"cab", b"deb", b"rpm",
-            b"pkg", b"z", b"lzh", b"cpio", b"tgz", b"tbz2", b"tlz", b"txz", b"jpg",
+            b"lzh", b"cpio", b"tgz", b"tbz2", b"tlz", b"txz", b"jpg",
             b"jpeg", b"png", b"gif", b"bmp", b"tiff", b"ico", b"mp3", b"aac", b"wav",
             b"flac", b"ogg", b"wma", b"m4a", b"mp4", b"mkv", b"mov", b"avi", b"wmv",
-            b"flv", b"webm", b"3gp", b"m2ts", b"mts", b"ts", b"resx", b"pdb", b"dat",
-            b"dll.config", b"exe.config", b"pak", b"binlog", b"woff", b"woff2", b"ttf",
-            b"eot", b"db", b"sqlite", b"sqlitedb", b"mdb", b"accdb", b"fdb", b"ndf",
-            b"bak", b"ldf", b"mdf", b"bcp", b"db3", b"frm", b"myd", b"ib", b"doc",
-            b"docx", b"xls", b"xlsx", b"ppt", b"pptx", b"pdf", b"psd", b"ai", b"eps",
-            b"indd", b"sketch", b"xcf", b"raw", b"svg", b"otf", b"swf", b"fla", b"cr2",
-            b"nef", b"dng", b"arw", b"orf", b"ptx", b"srf", b"pef", b"sr2", b"raf",
-            b"3ds", b"blend", b"fbx", b"obj", b"stl", b"dae", b"mmd", b"lwo", b"c4d",
-            b"dxf", b"step", b"iges", b"alembic", b"usd", b"usdaz", b"sbsar", b"vtf",
-            b"rlib", b"rmeta", b"d",
+            b"flv", b"webm", b"3gp", b"m2ts", b"pdb", b"pak", b"binlog", b"woff", b"woff2",
+            b"ttf", b"eot", b"sqlite", b"sqlitedb", b"mdb", b"accdb", b"db3",
+            b"doc", b"docx", b"xls", b"xlsx", b"ppt", b"pptx", b"pdf", b"psd",
+            b"indd", b"xcf", b"otf", b"swf", b"fla", b"cr2",
+            b"nef", b"dng", b"arw", b"orf", b"srf", b"pef", b"sr2", b"raf",
+            b"3ds", b"blend", b"fbx", b"c4d", b"sbsar", b"vtf",
+            b"rlib", b"rmeta",
         };
 
         let is_bin = e
             .extension()
-            .is_none_or(|ext| BINARY_EXTENSIONS.contains(ext.as_encoded_bytes()));
+            .is_some_and(|ext| BINARY_EXTENSIONS.contains(ext.as_encoded_bytes()));
 
         !is_bin
     }
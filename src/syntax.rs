@@ -0,0 +1,281 @@
+use crate::comment::Comment;
+
+use std::path::Path;
+
+/// Comment syntax of a language: the line markers and block delimiters the scanner looks for
+pub struct Syntax {
+    pub line  : &'static [&'static str],
+    pub block : &'static [(&'static str, &'static str)]
+}
+
+macro_rules! syntax {
+    ($name:ident, line: [$($line:expr),* $(,)?], block: [$($block:expr),* $(,)?]) => {
+        pub const $name: Self = Self {
+            line  : &[$($line),*],
+            block : &[$($block),*]
+        };
+    };
+}
+
+impl Syntax {
+    // used for files we know nothing about
+    syntax!(DEFAULT    , line: ["//", "--", "#"]         , block: [("/*", "*/")]);
+
+    syntax!(C          , line: ["//"]                    , block: [("/*", "*/")]);
+    syntax!(RUST       , line: ["///", "//!", "//"]      , block: [("/*", "*/")]);
+    syntax!(CSS        , line: []                        , block: [("/*", "*/")]);
+    syntax!(PHP        , line: ["//", "#"]               , block: [("/*", "*/")]);
+    syntax!(HCL        , line: ["#", "//"]               , block: [("/*", "*/")]);
+    syntax!(NIX        , line: ["#"]                     , block: [("/*", "*/")]);
+    syntax!(HASH       , line: ["#"]                     , block: []);
+    syntax!(CMAKE      , line: ["#"]                     , block: [("#[[", "]]")]);
+    syntax!(PYTHON     , line: ["#"]                     , block: [("\"\"\"", "\"\"\""), ("'''", "'''")]);
+    syntax!(JULIA      , line: ["#"]                     , block: [("#=", "=#")]);
+    syntax!(NIM        , line: ["#"]                     , block: [("#[", "]#")]);
+    syntax!(POWERSHELL , line: ["#"]                     , block: [("<#", "#>")]);
+    syntax!(COFFEE     , line: ["#"]                     , block: [("###", "###")]);
+    syntax!(SQL        , line: ["--"]                    , block: [("/*", "*/")]);
+    syntax!(LUA        , line: ["--"]                    , block: [("--[[", "]]")]);
+    syntax!(ADA        , line: ["--"]                    , block: []);
+    syntax!(HASKELL    , line: ["--"]                    , block: [("{-", "-}")]);
+    syntax!(LEAN       , line: ["--"]                    , block: [("/-", "-/")]);
+    syntax!(OCAML      , line: []                        , block: [("(*", "*)")]);
+    syntax!(FSHARP     , line: ["//"]                    , block: [("(*", "*)")]);
+    syntax!(PASCAL     , line: ["//"]                    , block: [("(*", "*)"), ("{", "}")]);
+    syntax!(LISP       , line: [";"]                     , block: [("#|", "|#")]);
+    syntax!(CLOJURE    , line: [";"]                     , block: []);
+    syntax!(ASM        , line: [";", "#"]                , block: [("/*", "*/")]);
+    syntax!(INI        , line: [";", "#"]                , block: []);
+    syntax!(TEX        , line: ["%"]                     , block: []);
+    syntax!(ERLANG     , line: ["%"]                     , block: []);
+    syntax!(MARKUP     , line: []                        , block: [("<!--", "-->")]);
+    syntax!(COMPONENT  , line: ["//"]                    , block: [("<!--", "-->"), ("/*", "*/")]);
+    syntax!(VB         , line: ["'", "REM", "Rem", "rem"], block: []);
+    syntax!(BATCH      , line: ["::", "REM", "@REM", "rem", "@rem"], block: []);
+    syntax!(FORTRAN    , line: ["!"]                     , block: []);
+    syntax!(VIM        , line: ["\""]                    , block: []);
+
+    // keyed by lowercased file extension
+    const BY_EXTENSION: phf::Map::<&'static str, &'static Self> = phf::phf_map! {
+        "c" => &Self::C, "h" => &Self::C, "cc" => &Self::C, "cpp" => &Self::C,
+        "cxx" => &Self::C, "c++" => &Self::C, "hh" => &Self::C, "hpp" => &Self::C,
+        "hxx" => &Self::C, "h++" => &Self::C, "ino" => &Self::C, "m" => &Self::C,
+        "mm" => &Self::C, "cu" => &Self::C, "cuh" => &Self::C, "java" => &Self::C,
+        "kt" => &Self::C, "kts" => &Self::C, "scala" => &Self::C, "sc" => &Self::C,
+        "groovy" => &Self::C, "gradle" => &Self::C, "js" => &Self::C, "mjs" => &Self::C,
+        "cjs" => &Self::C, "jsx" => &Self::C, "ts" => &Self::C, "mts" => &Self::C,
+        "cts" => &Self::C, "tsx" => &Self::C, "cs" => &Self::C, "go" => &Self::C,
+        "swift" => &Self::C, "dart" => &Self::C, "zig" => &Self::C, "odin" => &Self::C,
+        "v" => &Self::C, "sv" => &Self::C, "svh" => &Self::C, "proto" => &Self::C,
+        "sol" => &Self::C, "glsl" => &Self::C, "hlsl" => &Self::C, "vert" => &Self::C,
+        "frag" => &Self::C, "wgsl" => &Self::C, "json5" => &Self::C, "jsonc" => &Self::C,
+        "scss" => &Self::C, "less" => &Self::C, "jenkinsfile" => &Self::C, "hx" => &Self::C,
+        "d" => &Self::C, "di" => &Self::C,
+        "rs" => &Self::RUST,
+        "css" => &Self::CSS,
+        "php" => &Self::PHP,
+        "tf" => &Self::HCL, "tfvars" => &Self::HCL, "hcl" => &Self::HCL,
+        "nix" => &Self::NIX,
+        "sh" => &Self::HASH, "bash" => &Self::HASH, "zsh" => &Self::HASH, "fish" => &Self::HASH,
+        "ksh" => &Self::HASH, "rb" => &Self::HASH, "rake" => &Self::HASH, "gemspec" => &Self::HASH,
+        "pl" => &Self::HASH, "pm" => &Self::HASH, "r" => &Self::HASH, "toml" => &Self::HASH,
+        "yml" => &Self::HASH, "yaml" => &Self::HASH, "mk" => &Self::HASH, "mak" => &Self::HASH,
+        "make" => &Self::HASH, "dockerfile" => &Self::HASH, "ex" => &Self::HASH, "exs" => &Self::HASH,
+        "cr" => &Self::HASH, "tcl" => &Self::HASH, "awk" => &Self::HASH, "sed" => &Self::HASH,
+        "graphql" => &Self::HASH, "gql" => &Self::HASH, "properties" => &Self::HASH, "env" => &Self::HASH,
+        "bzl" => &Self::HASH, "bazel" => &Self::HASH, "star" => &Self::HASH, "just" => &Self::HASH,
+        "gitignore" => &Self::HASH, "gitattributes" => &Self::HASH, "dockerignore" => &Self::HASH,
+        "cmake" => &Self::CMAKE,
+        "py" => &Self::PYTHON, "pyi" => &Self::PYTHON, "pyw" => &Self::PYTHON, "pyx" => &Self::PYTHON,
+        "jl" => &Self::JULIA,
+        "nim" => &Self::NIM, "nims" => &Self::NIM,
+        "ps1" => &Self::POWERSHELL, "psm1" => &Self::POWERSHELL, "psd1" => &Self::POWERSHELL,
+        "coffee" => &Self::COFFEE,
+        "sql" => &Self::SQL, "psql" => &Self::SQL, "pgsql" => &Self::SQL,
+        "lua" => &Self::LUA,
+        "adb" => &Self::ADA, "ads" => &Self::ADA, "vhdl" => &Self::ADA,
+        "hs" => &Self::HASKELL, "lhs" => &Self::HASKELL, "elm" => &Self::HASKELL,
+        "purs" => &Self::HASKELL, "agda" => &Self::HASKELL, "idr" => &Self::HASKELL,
+        "lean" => &Self::LEAN,
+        "ml" => &Self::OCAML, "mli" => &Self::OCAML, "sml" => &Self::OCAML,
+        "fs" => &Self::FSHARP, "fsi" => &Self::FSHARP, "fsx" => &Self::FSHARP,
+        "pas" => &Self::PASCAL, "pp" => &Self::PASCAL, "dpr" => &Self::PASCAL, "lpr" => &Self::PASCAL,
+        "lisp" => &Self::LISP, "lsp" => &Self::LISP, "cl" => &Self::LISP, "el" => &Self::LISP,
+        "scm" => &Self::LISP, "ss" => &Self::LISP, "rkt" => &Self::LISP, "fnl" => &Self::LISP,
+        "clj" => &Self::CLOJURE, "cljs" => &Self::CLOJURE, "cljc" => &Self::CLOJURE, "edn" => &Self::CLOJURE,
+        "asm" => &Self::ASM, "s" => &Self::ASM, "nasm" => &Self::ASM,
+        "ini" => &Self::INI, "cfg" => &Self::INI, "conf" => &Self::INI, "editorconfig" => &Self::INI,
+        "tex" => &Self::TEX, "sty" => &Self::TEX, "cls" => &Self::TEX, "ltx" => &Self::TEX,
+        "bib" => &Self::TEX, "ps" => &Self::TEX,
+        "erl" => &Self::ERLANG, "hrl" => &Self::ERLANG,
+        "html" => &Self::MARKUP, "htm" => &Self::MARKUP, "xhtml" => &Self::MARKUP, "xml" => &Self::MARKUP,
+        "xsd" => &Self::MARKUP, "xsl" => &Self::MARKUP, "xslt" => &Self::MARKUP, "plist" => &Self::MARKUP,
+        "md" => &Self::MARKUP, "markdown" => &Self::MARKUP, "csproj" => &Self::MARKUP, "xaml" => &Self::MARKUP,
+        "svg" => &Self::MARKUP,
+        "vue" => &Self::COMPONENT, "svelte" => &Self::COMPONENT, "astro" => &Self::COMPONENT,
+        "vb" => &Self::VB, "vbs" => &Self::VB, "bas" => &Self::VB, "vba" => &Self::VB,
+        "bat" => &Self::BATCH, "cmd" => &Self::BATCH,
+        "f" => &Self::FORTRAN, "for" => &Self::FORTRAN, "f90" => &Self::FORTRAN, "f95" => &Self::FORTRAN,
+        "f03" => &Self::FORTRAN, "f08" => &Self::FORTRAN,
+        "vim" => &Self::VIM,
+    };
+
+    // well-known files without a (meaningful) extension
+    const BY_FILENAME: phf::Map::<&'static str, &'static Self> = phf::phf_map! {
+        "Makefile" => &Self::HASH, "makefile" => &Self::HASH, "GNUmakefile" => &Self::HASH,
+        "Dockerfile" => &Self::HASH, "Containerfile" => &Self::HASH, "CMakeLists.txt" => &Self::CMAKE,
+        "Jenkinsfile" => &Self::C, "Vagrantfile" => &Self::HASH, "Rakefile" => &Self::HASH,
+        "Gemfile" => &Self::HASH, "Podfile" => &Self::HASH, "Brewfile" => &Self::HASH,
+        "Justfile" => &Self::HASH, "justfile" => &Self::HASH, "Procfile" => &Self::HASH,
+        "BUILD" => &Self::HASH, "WORKSPACE" => &Self::HASH, "Tiltfile" => &Self::HASH,
+        "Snakefile" => &Self::PYTHON, "SConstruct" => &Self::PYTHON, "SConscript" => &Self::PYTHON,
+        ".bashrc" => &Self::HASH, ".zshrc" => &Self::HASH, ".profile" => &Self::HASH,
+        ".bash_profile" => &Self::HASH, ".env" => &Self::HASH, ".vimrc" => &Self::VIM,
+    };
+
+    #[inline]
+    #[must_use]
+    pub fn from_filename(path: &Path) -> Option<&'static Self> {
+        let file_name = path.file_name()?.to_str()?;
+        Self::BY_FILENAME.get(file_name).copied()
+    }
+
+    /// Looks up the syntax by well-known filename first, then by extension,
+    /// falling back to [`Syntax::DEFAULT`].
+    #[inline]
+    #[must_use]
+    pub fn from_path(path: &Path) -> &'static Self {
+        if let Some(syntax) = Self::from_filename(path) {
+            return syntax
+        }
+
+        let Some(ext) = path.extension().and_then(|e| e.to_str()) else {
+            return &Self::DEFAULT
+        };
+
+        let get = |ext: &str| Self::BY_EXTENSION.get(ext).copied();
+
+        let syntax = if ext.bytes().any(|b| b.is_ascii_uppercase()) {
+            get(&ext.to_ascii_lowercase())
+        } else {
+            get(ext)
+        };
+
+        syntax.unwrap_or(&Self::DEFAULT)
+    }
+
+    /// Returns the block comment left open at the end of `line`, scanning from `from`.
+    #[must_use]
+    pub fn open_block_after(&self, line: &[u8], mut from: usize) -> Option<Comment> {
+        while let Some((pos, comment)) = self.find_comment_start(&line[from..]) {
+            let Comment::Block { open, close } = comment else {
+                return None // the rest of the line is a line comment
+            };
+
+            let content_start = from + pos + open.len();
+
+            let Some(close_pos) = memchr::memmem::find(
+                &line[content_start..],
+                close.as_bytes()
+            ) else {
+                return Some(comment)
+            };
+
+            from = content_start + close_pos + close.len();
+        }
+
+        None
+    }
+
+    /// Finds the first comment opener in `line` that is not inside of a string literal.
+    /// On ties, the longest opener wins.
+    ///
+    /// Returns: (offset of the opener, comment kind)
+    #[must_use]
+    pub fn find_comment_start(&self, line: &[u8]) -> Option<(usize, Comment)> {
+        let mut i = 0;
+
+        while i < line.len() {
+            let prev = i.checked_sub(1).map(|p| line[p]);
+
+            if let Some(comment) = self.comment_at(prev, &line[i..]) {
+                return Some((i, comment))
+            }
+
+            i = match line[i] {
+                b'"'  => Self::skip_string_literal(line, i),
+                b'\'' => Self::skip_char_literal(line, i),
+                _     => i + 1
+            };
+        }
+
+        None
+    }
+
+    // `prev` is the byte right before `h`, if any
+    #[inline]
+    fn comment_at(&self, prev: Option<u8>, h: &[u8]) -> Option<Comment> {
+        let block = self.block
+            .iter()
+            .filter(|(open, _)| is_marker_at(open, prev, h))
+            .max_by_key(|(open, _)| open.len())
+            .map(|&(open, close)| Comment::Block { open, close });
+
+        let line = self.line
+            .iter()
+            .filter(|marker| is_marker_at(marker, prev, h))
+            .max_by_key(|marker| marker.len())
+            .map(|&marker| Comment::Line(marker));
+
+        match (block, line) {
+            (Some(b), Some(l)) => Some(if b.opener().len() >= l.opener().len() { b } else { l }),
+            (b, l) => b.or(l)
+        }
+    }
+
+    // returns the offset right after the closing quote (or the end of the line)
+    #[inline]
+    fn skip_string_literal(line: &[u8], start: usize) -> usize {
+        let mut i = start + 1;
+        while i < line.len() {
+            match line[i] {
+                b'\\' => i += 2,
+                b'"'  => return i + 1,
+                _     => i += 1
+            }
+        }
+
+        line.len()
+    }
+
+    // only skips things that look like char literals: 'x' or '\x',
+    // so that lifetimes and apostrophes in prose are left alone
+    #[inline]
+    fn skip_char_literal(line: &[u8], start: usize) -> usize {
+        match line.get(start + 1..) {
+            Some([b'\\', _, b'\'', ..]) => start + 4,
+            Some([_, b'\'', ..])        => start + 3,
+            _ => start + 1
+        }
+    }
+}
+
+/// Whether `h` starts with the comment marker `marker`, `prev` being the byte before it.
+/// Markers that are words, like `REM`, only count as a token of their own,
+/// so that `REMOTE` or `remaining` don't start a comment.
+#[inline]
+#[must_use]
+pub fn is_marker_at(marker: &str, prev: Option<u8>, h: &[u8]) -> bool {
+    if !h.starts_with(marker.as_bytes()) {
+        return false
+    }
+
+    let is_ident = |b: &u8| b.is_ascii_alphanumeric() || *b == b'_';
+
+    if !marker.as_bytes().last().is_some_and(is_ident) {
+        return true
+    }
+
+    prev.as_ref().is_none_or(|b| !is_ident(b))
+        && h.get(marker.len()).is_none_or(u8::is_ascii_whitespace)
+}
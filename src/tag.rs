@@ -1,12 +1,16 @@
+use crate::util;
+use crate::hunk::{Hunk, LineMaps};
 use crate::todo::Todo;
 use crate::purge::Purges;
 use crate::config::Config;
-use crate::fm::{FileId, FileManager};
+use crate::fm::{self, FileId, FileManager};
 
 use std::{mem, fmt};
+use std::path::Path;
 use std::sync::Arc;
 use std::sync::atomic::{Ordering, AtomicUsize};
 
+use anyhow::Context;
 use futures::StreamExt;
 use tokio::sync::mpsc::UnboundedReceiver;
 use tokio_stream::wrappers::UnboundedReceiverStream;
@@ -31,11 +35,24 @@ impl fmt::Display for Tag {
 }
 
 impl Tag {
+    /// What replaces the metadata groups after the keyword:
+    /// `(#12)`, or `(#12)[@alice, bug]` if the metadata is kept
+    #[inline]
+    #[must_use]
+    pub fn replacement(&self, keep_metadata: bool) -> String {
+        if keep_metadata && !self.todo.meta.is_empty() {
+            format!("{self}{meta}", meta = self.todo.meta)
+        } else {
+            self.to_string()
+        }
+    }
+
     #[inline(always)]
     #[must_use]
     pub fn commit_msg(&self) -> String {
         format!{
-            "Add TODO{self}: {t}",
+            "Add {k}{self}: {t}",
+            k = self.todo.keyword,
             t = self.todo.title
         }
     }
@@ -74,6 +91,7 @@ impl TagInserter {
                     match inserter_value {
                         InserterValue::Inserting(file_id) => {
                             if let Err(err) = inserter.insert_tags(file_id) {
+                                inserter.config.git_locker.mark_failed();
                                 eprintln!{
                                     "[tag] failed to insert tagʼs for file {file_id:?}: {err:#}"
                                 }
@@ -88,6 +106,7 @@ impl TagInserter {
                                 &inserter.config,
                                 &inserter.fm
                             ) {
+                                inserter.config.git_locker.mark_failed();
                                 eprintln!{
                                     "[tag] failed to purge todoʼs for file {file_id:?}: {err:#}"
                                 }
@@ -108,53 +127,88 @@ impl TagInserter {
 
         if insertions.is_empty() { return Ok(()) }
 
+        let mut ranges = insertions.iter().map(|t| {
+            t.todo.tag_insertion_offset..t.todo.tag_insertion_offset + t.todo.tag_replace_len
+        }).collect::<Vec<_>>();
+
+        let mut buf = self.fm.read_for_edit(file_id, &mut ranges)?;
+
+        // to undo the edit if the per-run commit can't be made
+        let original = self.config.git_locker.keeps_originals().then(|| buf.clone());
+
+        for (tag, range) in insertions.iter_mut().zip(ranges) {
+            tag.todo.tag_insertion_offset = range.start;
+        }
+
         // sort ascending so that all prior inserts were at <= current offset
-        insertions.sort_by(|a, b| a.todo.tag_insertion_offset.cmp(&b.todo.tag_insertion_offset));
+        insertions.sort_by_key(|t| t.todo.tag_insertion_offset);
+
+        let keep_metadata = self.config.settings.issues.keep_metadata;
 
         let insertions = insertions.into_iter().map(|t| {
-            (t.to_string(), t)
+            (t.replacement(keep_metadata), t)
         }).collect::<Vec<_>>();
 
-        let total_insert_len = insertions
-            .iter()
-            .map(|(s, _)| s.len())
-            .sum::<usize>();
+        let file_path = self.fm.get_file_path_unchecked(file_id).to_owned();
 
-        let orig_len = self.fm.get_file_unchecked(file_id).meta.len() as usize;
-        let new_len = orig_len + total_insert_len;
+        let orig_len = buf.len();
 
-        let file_path = self.fm.get_file_path_unchecked(file_id).to_owned();
+        let git_locker = &self.config.git_locker;
+
+        // every commit needs the file as it is after its tag, otherwise it's written once at the end
+        let writes_every_tag = git_locker.commits_every_tag();
 
-        let mut mmap = self.fm.get_mmap_or_remmap_file_mut(file_id, new_len)?;
+        let mut msgs = Vec::with_capacity(insertions.len());
 
-        let mut shift = 0;
+        // only kept if they are going to be staged instead of the whole file
+        let needs_hunks = git_locker.needs_hunks(&file_path);
+        let mut hunks = Vec::new();
+
+        let line_maps = if needs_hunks { git_locker.line_maps(&file_path)? } else { LineMaps::default() };
 
         for (tag_str, tag) in insertions {
-            let byte_offset = tag.todo.tag_insertion_offset;
             let insert_bytes = tag_str.as_bytes();
-            let tag_len = insert_bytes.len();
-
-            let actual_offset = byte_offset + shift;
+            let replace_len = tag.todo.tag_replace_len;
 
-            mmap.copy_within(
-                actual_offset..orig_len + shift,
-                actual_offset + tag_len,
-            );
+            // all prior tags were at <= current offset
+            let actual_offset = tag.todo.tag_insertion_offset + buf.len() - orig_len;
+            let replaced = actual_offset..actual_offset + replace_len;
 
-            mmap[actual_offset..actual_offset + tag_len]
-                .copy_from_slice(insert_bytes);
+            let hunk = if needs_hunks {
+                let hunk = Hunk::new(&buf, replaced.clone(), insert_bytes, &line_maps);
+                Some(hunk.with_context(|| format!{
+                    "the lines of a TODO in {file_path} are part of your uncommitted changes, \
+                     so stalkr's change of them can't be staged on its own"
+                })?)
+            } else {
+                None
+            };
 
-            mmap.flush()?;
+            buf.splice(replaced, insert_bytes.iter().copied());
 
             let msg = tag.commit_msg();
-            self.config.git_locker.commit_changes(&file_path, &msg)?;
 
-            self.processed_count.fetch_add(1, Ordering::SeqCst);
+            if writes_every_tag {
+                fm::write_atomically(Path::new(&file_path), &buf)?;
+                git_locker.commit_tag(&file_path, &msg, hunk.as_slice())?;
+                self.processed_count.fetch_add(1, Ordering::SeqCst);
+            }
+
+            msgs.push(msg);
+            hunks.extend(hunk);
 
-            shift += tag_len;
+            self.config.tagged_issues.lock().unwrap().push((tag.issue_number, tag.todo.title.clone()));
         }
 
-        Ok(())
+        if !writes_every_tag {
+            fm::write_atomically(Path::new(&file_path), &buf)?;
+            self.processed_count.fetch_add(msgs.len(), Ordering::SeqCst);
+        }
+
+        let name = util::relative_path(&self.config.cwd, &file_path);
+        let subject = format!("Add {n} issue tags to {name}", n = msgs.len());
+
+        self.config.git_locker.commit_file(&file_path, &name, &subject, msgs, hunks, original)
     }
 }
 
@@ -0,0 +1,247 @@
+use crate::git;
+use crate::util;
+use crate::todo::Todo;
+use crate::config::Config;
+use crate::fm::FileManager;
+
+use std::path::Path;
+use std::fmt::Write;
+
+use anyhow::bail;
+
+/// Variables that can be used in issue templates as `{{name}}`
+#[derive(Eq, Copy, Clone, Debug, PartialEq)]
+pub enum Var {
+    /// Title of the TODO, with the keyword's `title_prefix`
+    Title,
+    Path,
+    Line,
+    Keyword,
+    Description,
+    /// Lines around the TODO
+    Context,
+    /// Extension of the file, to highlight `context` in a code block
+    Lang,
+    Sha,
+    Branch,
+    /// Who last touched the line of the TODO, according to `git blame`
+    Author,
+    /// Link to the line at `sha`, or `path:line` if the tracker has no web ui
+    Permalink
+}
+
+impl Var {
+    pub const ALL: [Self; 11] = [
+        Self::Title, Self::Path, Self::Line, Self::Keyword, Self::Description, Self::Context,
+        Self::Lang, Self::Sha, Self::Branch, Self::Author, Self::Permalink
+    ];
+
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Title       => "title",
+            Self::Path        => "path",
+            Self::Line        => "line",
+            Self::Keyword     => "keyword",
+            Self::Description => "description",
+            Self::Context     => "context",
+            Self::Lang        => "lang",
+            Self::Sha         => "sha",
+            Self::Branch      => "branch",
+            Self::Author      => "author",
+            Self::Permalink   => "permalink"
+        }
+    }
+
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|v| v.name() == name)
+    }
+}
+
+/// Rendered title and body of an issue
+#[derive(Debug)]
+pub struct IssueText {
+    pub title: String,
+    pub body: String,
+
+    /// `path:line` of the TODO, see [`crate::index`]
+    pub location: String
+}
+
+impl IssueText {
+    /// Renders the templates of the settings for `todo` at `location`
+    #[must_use]
+    pub fn render(config: &Config, fm: &FileManager, todo: &Todo, location: String) -> Self {
+        let issues = &config.settings.issues;
+
+        let [title, body] = Self::render_templates(config, fm, todo, &location, [&issues.title, &issues.body]);
+
+        Self {
+            title: title.replace('\n', " "),
+            body,
+            location
+        }
+    }
+
+    /// Renders only the title, e.g. to look up the issue of `todo`,
+    /// without the cost of what only the body uses, like `git blame`
+    #[must_use]
+    pub fn render_title(config: &Config, fm: &FileManager, todo: &Todo, location: &str) -> String {
+        let [title] = Self::render_templates(config, fm, todo, location, [&config.settings.issues.title]);
+        title.replace('\n', " ")
+    }
+
+    // only computes the variables that `templates` use
+    fn render_templates<const N: usize>(
+        config: &Config,
+        fm: &FileManager,
+        todo: &Todo,
+        location: &str,
+        templates: [&Template; N]
+    ) -> [String; N] {
+        let issues = &config.settings.issues;
+        let sha = config.head.sha.as_deref();
+        let branch = config.head.branch.as_deref();
+
+        let file_id = todo.loc.file_id();
+        let line = todo.loc.line_number();
+
+        let path = util::relative_path(&config.cwd, &fm.get_file_path_unchecked(file_id));
+
+        let uses = |var| templates.iter().any(|t| t.uses(var));
+
+        let context = if uses(Var::Context) {
+            fm.get_file_unchecked(file_id).lines_around(line, issues.context_lines)
+        } else {
+            String::new()
+        };
+
+        let author = if uses(Var::Author) {
+            git::blame_author(&config.cwd, &path, line)
+        } else {
+            None
+        };
+
+        let permalink = sha
+            .and_then(|sha| config.api.get_permalink(config, sha, &path, line))
+            .unwrap_or_else(|| location.to_owned());
+
+        let value_of = |var| match var {
+            Var::Title       => todo.issue_title(),
+            Var::Path        => path.clone(),
+            Var::Line        => line.to_string(),
+            Var::Keyword     => todo.keyword.name.to_string(),
+            Var::Context     => context.clone(),
+            Var::Permalink   => permalink.clone(),
+            Var::Sha         => sha.unwrap_or_default().to_owned(),
+            Var::Branch      => branch.unwrap_or_default().to_owned(),
+            Var::Author      => author.as_deref().unwrap_or_default().to_owned(),
+            Var::Lang        => Path::new(&path).extension()
+                .map(|ext| ext.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            Var::Description => todo.description.as_ref()
+                .map(|d| d.lines.join("\n"))
+                .unwrap_or_default(),
+        };
+
+        templates.map(|t| t.render(value_of))
+    }
+}
+
+#[derive(Debug)]
+enum Part {
+    Text(Box<str>),
+    Var(Var)
+}
+
+/// Title or body of an issue with `{{name}}` placeholders, see [`Var`]
+#[derive(Debug)]
+pub struct Template {
+    parts: Vec<Part>
+}
+
+impl Template {
+    pub const DEFAULT_TITLE: &str = "{{title}}";
+
+    pub const DEFAULT_BODY: &str = "\
+{{description}}
+
+{{permalink}}
+
+```{{lang}}
+{{context}}
+```";
+
+    pub fn parse(s: &str) -> anyhow::Result<Self> {
+        let mut parts = Vec::new();
+        let mut rest = s;
+
+        while let Some(start) = rest.find("{{") {
+            let Some(len) = rest[start..].find("}}") else { break };
+
+            let name = rest[start + "{{".len()..start + len].trim();
+
+            let Some(var) = Var::from_name(name) else {
+                let names = Var::ALL.map(Var::name);
+                bail!("unknown variable {{{{{name}}}}}, expected one of: {}", names.join(", "))
+            };
+
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].into()));
+            }
+
+            parts.push(Part::Var(var));
+
+            rest = &rest[start + len + "}}".len()..];
+        }
+
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.into()));
+        }
+
+        Ok(Self { parts })
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn uses(&self, var: Var) -> bool {
+        self.parts.iter().any(|p| matches!(p, Part::Var(v) if *v == var))
+    }
+
+    /// Runs of blank lines that are left from empty variables are collapsed,
+    /// except inside of code blocks
+    #[must_use]
+    pub fn render(&self, value_of: impl Fn(Var) -> String) -> String {
+        let mut rendered = String::new();
+
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => rendered.push_str(text),
+                Part::Var(var) => rendered.push_str(&value_of(*var))
+            }
+        }
+
+        let mut out = String::with_capacity(rendered.len());
+        let mut blank_lines = 0;
+        let mut is_in_code = false;
+
+        for line in rendered.trim().lines() {
+            if line.trim_start().starts_with("```") {
+                is_in_code = !is_in_code;
+            }
+
+            if line.trim().is_empty() && !is_in_code {
+                blank_lines += 1;
+                if blank_lines > 1 { continue }
+            } else {
+                blank_lines = 0;
+            }
+
+            _ = writeln!(out, "{line}");
+        }
+
+        out.truncate(out.trim_end().len());
+        out
+    }
+}
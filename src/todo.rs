@@ -1,7 +1,13 @@
-use crate::{comment::Comment, util};
+use crate::util;
 use crate::loc::Loc;
+use crate::index;
+use crate::meta::Meta;
+use crate::template::IssueText;
+use crate::comment::Comment;
+use crate::keyword::{Keyword, Keywords};
 
 use std::{fmt, str};
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub struct Description {
@@ -45,77 +51,175 @@ impl fmt::Display for DisplayDescription<'_> {
     }
 }
 
+/// What [`Todo::extract_todo_head`] found after the keyword
+#[derive(Debug)]
+pub struct TodoHead<'a> {
+    pub title: &'a str,
+    pub is_tagged: bool,
+    /// `None` if untagged or if the tag couldn't be parsed
+    pub issue_number: Option<u64>,
+    pub meta: Meta,
+    /// Length of the `(...)` and `[...]` groups between the keyword and the `:`
+    pub groups_len: usize
+}
+
 #[derive(Debug)]
 pub struct Todo {
     pub loc: Loc,
+    pub keyword: Arc<Keyword>,
     #[allow(unused)]
     pub preview: Box<str>,
     pub title: Box<str>,
+    pub is_tagged: bool,
+    /// Issue number of a tagged TODO, `None` if untagged or if the tag couldn't be parsed
+    pub issue_number: Option<u64>,
     pub tag_insertion_offset: usize,
+    /// Length of the metadata groups at `tag_insertion_offset` that the tag replaces
+    pub tag_replace_len: usize,
+    pub meta: Meta,
     pub description: Option<Description>
 }
 
 impl Todo {
+    /// Title of the issue, with the keyword's title prefix
     #[inline]
-    #[must_use] 
-    pub fn as_json_value(&self) -> serde_json::Value {
-        serde_json::json!({
-            "title": self.title,
-            "body": self.description.as_ref().map(|ls| ls.lines.join("\n"))
-        })
+    #[must_use]
+    pub fn issue_title(&self) -> String {
+        match &self.keyword.title_prefix {
+            Some(prefix) => format!("{prefix}{title}", title = self.title),
+            None => self.title.to_string()
+        }
+    }
+
+    /// Labels of the issue: `default_labels`, then the keyword's, then the inline ones
+    #[must_use]
+    pub fn issue_labels<'a>(&'a self, default_labels: &'a [Box<str>]) -> Vec<&'a str> {
+        let mut labels = Vec::with_capacity(
+            default_labels.len() + self.keyword.labels.len() + self.meta.labels.len()
+        );
+
+        let all = default_labels.iter().chain(&*self.keyword.labels).chain(&self.meta.labels);
+
+        for label in all {
+            if !labels.contains(&&**label) {
+                labels.push(&**label);
+            }
+        }
+
+        labels
     }
 
-    /// Returns: (todo's title, is todo tagged or not)
+    /// Issue payload, `text.location` ends up in a hidden marker at the end of the body.
+    /// The milestone is passed by name, backends resolve it.
     #[inline]
-    #[must_use] 
-    pub fn extract_todo_title(h: &str) -> (&str, bool) {
-        let mut s = util::trim_comment_start(h).trim_start();
-        let mut is_tagged = false;
+    #[must_use]
+    pub fn as_json_value(&self, text: &IssueText, default_labels: &[Box<str>]) -> serde_json::Value {
+        let marker = index::make_location_marker(&text.location);
+
+        let body = if text.body.is_empty() {
+            marker
+        } else {
+            format!("{body}\n\n{marker}", body = text.body)
+        };
+
+        let mut json = serde_json::json!({
+            "title": text.title,
+            "body": body
+        });
+
+        let labels = self.issue_labels(default_labels);
+        if !labels.is_empty() {
+            json["labels"] = serde_json::json!(labels);
+        }
 
-        if let Some(rest) = s.strip_prefix("TODO") {
-            let rest = rest.trim_start();
+        if !self.meta.assignees.is_empty() {
+            json["assignees"] = serde_json::json!(self.meta.assignees);
+        }
 
-            if let Some(after_colon) = rest.strip_prefix(':') {
-                // e.g. "TODO:"
-                s = after_colon.trim_start();
-            } else if let Some(stripped) = Self::strip_todo_parens(rest) {
-                // e.g. "TODO(<...>):"
-                s = stripped;
-                is_tagged = true;
+        if let Some(milestone) = &self.meta.milestone {
+            json["milestone"] = serde_json::json!(milestone);
+        }
+
+        json
+    }
+
+    /// Parses what follows the comment marker: `<KEYWORD>:`, `<KEYWORD>(#n):`,
+    /// optionally with metadata, e.g. `<KEYWORD>(@alice, bug):` or `<KEYWORD>(#n)[label=perf]:`
+    ///
+    /// Returns: `None` if there's no `:` after the keyword and its groups
+    #[inline]
+    #[must_use]
+    pub fn extract_todo_head<'a>(h: &'a str, keyword: &str) -> Option<TodoHead<'a>> {
+        let s = util::trim_comment_start(h).trim_start();
+
+        let rest = s.strip_prefix(keyword)?;
+
+        // spaces are allowed before the groups and the `:`, e.g. `TODO : fix`
+        let skip_spaces = |s: &'a str| s.trim_start_matches([' ', '\t']);
+
+        let mut after_groups = skip_spaces(rest);
+        let mut is_tagged = false;
+        let mut issue_number = None;
+        let mut meta = Meta::default();
+
+        if let Some((inner, after)) = Self::strip_group(after_groups, b'(', b')') {
+            // e.g. "TODO(#12):", anything that isn't metadata is a (malformed) tag.
+            // Bare words alone, e.g. the owner in "TODO(alice):", aren't taken for labels,
+            // it takes an `@name` or a `key=value` for the group to be metadata.
+            let is_meta = inner.split(',').map(str::trim).any(|i| i.starts_with('@') || i.contains('='));
+
+            match Meta::parse(inner) {
+                Some(m) if is_meta && !inner.trim_start().starts_with('#') => meta = m,
+                _ => {
+                    is_tagged = true;
+                    issue_number = inner.trim().strip_prefix('#').and_then(|n| n.parse().ok());
+                }
             }
+
+            after_groups = skip_spaces(after);
         }
 
-        // trailing "*/"
-        s = s.trim_end_matches("*/").trim();
+        if let Some((inner, after)) = Self::strip_group(after_groups, b'[', b']') {
+            // e.g. "TODO[label=perf]:"
+            meta.merge(Meta::parse(inner)?);
+            after_groups = skip_spaces(after);
+        }
 
-        (s, is_tagged)
+        let title = after_groups
+            .strip_prefix(':')?
+            .trim_start()
+            .trim_end_matches("*/") // trailing "*/"
+            .trim();
+
+        Some(TodoHead {
+            title,
+            is_tagged,
+            issue_number,
+            meta,
+            groups_len: rest.len() - after_groups.len()
+        })
     }
 
-    // Helper: parse TODO(<...>): and return what's after it
+    // Helper: parse <open><...><close> and return (<...>, what's after it)
     #[inline]
-    fn strip_todo_parens(s: &str) -> Option<&str> {
+    fn strip_group(s: &str, open: u8, close: u8) -> Option<(&str, &str)> {
         let bytes = s.as_bytes();
-        if bytes.first() != Some(&b'(') {
+        if bytes.first() != Some(&open) {
             return None
         }
 
-        // find closing ')' that ends the TODO(...)
-        if let Some(end_paren) = memchr::memchr(b')', &bytes[1..]) {
-            let after_paren = &s[end_paren + 2..]; // +1 for offset, +1 for ')'
-            if let Some(stripped) = after_paren.strip_prefix(':') {
-                return Some(stripped.trim_start()) // skip ':'
-            }
-        }
+        let end = memchr::memchr(close, &bytes[1..])? + 1;
 
-        None
+        Some((&s[1..end], &s[end + 1..]))
     }
 
     /// Returns: (Description, index of the last newline in the last descriptionl line)
     #[inline]
-    #[must_use] 
+    #[must_use]
     pub fn extract_todo_description(
         h: &[u8],
-        comment: Comment
+        comment: Comment,
+        keywords: &Keywords
     ) -> Option<(Description, usize)> {
         let mut lines = Vec::with_capacity(4);
 
@@ -134,28 +238,26 @@ impl Todo {
 
             start = line_end;
 
-            let line_str = unsafe {
-                str::from_utf8_unchecked(line)
+            let Ok(line_str) = str::from_utf8(line) else {
+                break
             };
 
-            if comment.is_line_a_comment(line_str).is_none() {
+            let Some(content_start) = comment.is_line_a_comment(line_str) else {
                 break
-            }
+            };
 
-            let line_str = util::trim_comment_start(line_str);
+            let line_str = line_str[content_start..].trim();
 
             if line_str.is_empty() {
                 break
             }
 
-            if ["TODO:", "TODO("].iter().any(|p| line_str.starts_with(p)) {
+            if keywords.match_start(line_str).is_some() {
                 break
             }
 
             end = line_end;
 
-            let line_str = line_str.strip_suffix('\n').unwrap_or(line_str);
-
             let line_str = util::string_into_boxed_str_norealloc(
                 line_str.to_owned()
             );
@@ -170,4 +272,92 @@ impl Todo {
         let lines = util::vec_into_boxed_slice_norealloc(lines);
         Some((Description { lines }, end))
     }
+
+    /// Same as [`Todo::extract_todo_description`], but for TODO's inside of a block comment
+    /// that is still open. The description ends at the block's closer.
+    ///
+    /// Returns: (Description and the end of its last line, closer of the block if reached)
+    #[inline]
+    #[must_use]
+    pub fn extract_block_todo_description(
+        h: &[u8],
+        comment: Comment,
+        keywords: &Keywords
+    ) -> (Option<(Description, usize)>, Option<BlockClose>) {
+        let Some(close) = comment.closer() else {
+            return (None, None)
+        };
+
+        let mut lines = Vec::with_capacity(4);
+
+        let mut start = 0;
+        let mut end   = 0;
+
+        let mut block_close = None;
+
+        while start < h.len() {
+            let nl_rel = memchr::memchr(b'\n', &h[start..]);
+            let line_end = match nl_rel {
+                Some(rel) => start + rel + 1,
+                None      => h.len(),
+            };
+
+            let line_start = start;
+            let line = &h[start..line_end];
+
+            start = line_end;
+
+            let Ok(line_str) = str::from_utf8(line) else {
+                break
+            };
+
+            let content_start = comment.block_line_content_start(line_str);
+
+            let close_pos = line_str[content_start..]
+                .find(close)
+                .map(|p| content_start + p);
+
+            let content_end = close_pos.unwrap_or(line_str.len());
+
+            let content = line_str[content_start..content_end.max(content_start)].trim();
+
+            if let Some(close_pos) = close_pos {
+                block_close = Some(BlockClose {
+                    start    : line_start + close_pos,
+                    end      : line_start + close_pos + close.len(),
+                    line_end
+                });
+            }
+
+            if content.is_empty() || keywords.match_start(content).is_some() {
+                if !content.is_empty() { block_close = None }
+                break
+            }
+
+            end = line_end;
+
+            lines.push(util::string_into_boxed_str_norealloc(content.to_owned()));
+
+            if close_pos.is_some() {
+                break
+            }
+        }
+
+        let description = if lines.is_empty() {
+            None
+        } else {
+            let lines = util::vec_into_boxed_slice_norealloc(lines);
+            Some((Description { lines }, end))
+        };
+
+        (description, block_close)
+    }
+}
+
+/// Location of a block comment closer, relative to the haystack it was found in
+#[derive(Copy, Clone, Debug)]
+pub struct BlockClose {
+    pub start: usize,
+    pub end: usize,
+    pub line_end: usize
 }
@@ -1,6 +1,8 @@
-use std::path::{Path, PathBuf};
+use crate::git::Repo;
+
+use std::path::{Path, Component};
 use std::borrow::Cow;
-use std::{fs, mem, slice, str};
+use std::{fs, mem, ptr, slice, str};
 use std::io::{self, Write};
 
 #[inline]
@@ -69,7 +71,7 @@ pub fn vec_into_boxed_slice_norealloc<T>(mut v: Vec<T>) -> Box<[T]> {
     mem::forget(v);
 
     unsafe {
-        Box::from_raw(slice::from_raw_parts_mut(ptr, len))
+        Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, len))
     }
 }
 
@@ -117,69 +119,119 @@ pub fn balance_concurrency(cpu_count: usize) -> (usize, usize) {
     (rayon_threads, max_concurrency)
 }
 
+/// A parsed git remote, e.g. `git@gitlab.example.com:group/subgroup/project.git`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteUrl {
+    /// Host of the remote, with the port for http(s) remotes, e.g. `gitlab.example.com:8443`
+    pub host: String,
+
+    /// Web url of the host: the scheme is kept for http(s) remotes, `https` otherwise
+    pub base_url: String,
+
+    /// Everything before the last path segment, e.g. `group/subgroup`
+    pub owner: String,
+
+    pub repo: String
+}
+
+/// Parses http(s), ssh, git and scp-like (`user@host:path`) remote urls
+#[must_use]
+pub fn parse_remote_url(url: &str) -> Option<RemoteUrl> {
+    let url = url.trim();
+
+    let (host, path, web_scheme) = if let Some((scheme, rest)) = url.split_once("://") {
+        let (authority, path) = rest.split_once('/')?;
+        let scheme = scheme.to_ascii_lowercase();
+        let web_scheme = matches!(scheme.as_str(), "http" | "https").then_some(scheme);
+        (authority, path, web_scheme)
+    } else {
+        // scp-like syntax: [user@]host:path
+        let (authority, path) = url.split_once(':')?;
+        if authority.contains('/') { return None }
+        (authority, path, None)
+    };
+
+    // strip the userinfo
+    let host = host.rsplit_once('@').map_or(host, |(_, host)| host);
+    let host = if web_scheme.is_some() { host } else { host.split(':').next()? };
+
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+
+    let (owner, repo) = path.rsplit_once('/')?;
+
+    if host.is_empty() || owner.is_empty() || repo.is_empty() {
+        return None
+    }
+
+    let host = host.to_ascii_lowercase();
+
+    Some(RemoteUrl {
+        base_url: format!("{s}://{host}", s = web_scheme.as_deref().unwrap_or("https")),
+        host,
+        owner: owner.to_owned(),
+        repo: repo.to_owned()
+    })
+}
+
+#[inline]
 #[must_use]
 pub fn parse_owner_repo(url: &str) -> Option<(String, String)> {
-    // find the "github.com/" or "github.com:" pivot
-    let pivot = url.find("github.com/").or_else(|| url.find("github.com:"))?;
-    // slice just after the slash/colon
-    let rest = &url[pivot + "github.com/".len()..];
-    // split into owner and repo.git?
-    let mut parts = rest.splitn(2, '/');
-
-    let owner = parts.next()?.to_owned();
-    let mut repo = parts.next()?.to_owned();
-
-    // strip optional ".git" suffix
-    if Path::new(&repo)
-        .extension()
-        .is_some_and(|ext| ext.eq_ignore_ascii_case("git"))
-    {
-        repo.truncate(repo.len() - 4);
+    parse_remote_url(url).map(|RemoteUrl { owner, repo, .. }| (owner, repo))
+}
+
+/// Percent-encodes every byte of `s` that isn't alphanumeric or in `keep`
+#[must_use]
+pub fn percent_encode(s: &str, keep: &[u8]) -> String {
+    use std::fmt::Write;
+
+    let mut encoded = String::with_capacity(s.len());
+
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || keep.contains(&b) {
+            encoded.push(b as char);
+        } else {
+            _ = write!(encoded, "%{b:02X}");
+        }
     }
 
-    Some((owner, repo))
+    encoded
 }
 
+/// Url of `remote` of the repository `dir` is in, see [`Repo::discover`]
 #[must_use]
-pub fn get_git_origin_url(mut dir: PathBuf, remote: &str) -> Option<String> {
-    loop {
-        let config = dir.join(".git/config");
-        if config.exists() {
-            let contents = fs::read_to_string(&config).ok()?;
-
-            // First, try to find the requested remote
-            if let Some(url) = find_remote_url(&contents, remote) {
-                return Some(url);
-            }
-
-            // Fallback 1: Try pushDefault
-            if let Some(push_default) = find_push_default(&contents) {
-                eprintln!("[falling back to pushDefault]: {push_default}");
-                if let Some(url) = find_remote_url(&contents, &push_default) {
-                    return Some(url);
-                }
-            }
-
-            // Fallback 2: Try current branch's remote
-            if let Some(branch_remote) = find_current_branch_remote(&dir, &contents) {
-                println!("[falling back to branch remote]: {branch_remote}");
-                if let Some(url) = find_remote_url(&contents, &branch_remote) {
-                    return Some(url);
-                }
-            }
-
-            // Fallback 3: Use any available remote
-            if let Some(url) = find_any_remote_url(&contents) {
-                println!("[falling back to first available remote]");
-                return Some(url);
-            }
+pub fn get_git_origin_url(dir: &Path, remote: &str) -> Option<String> {
+    let repo = Repo::discover(dir)?;
 
-            break;
+    let contents = fs::read_to_string(repo.common_dir.join("config")).ok()?;
+
+    // First, try to find the requested remote
+    if let Some(url) = find_remote_url(&contents, remote) {
+        return Some(url);
+    }
+
+    // Fallback 1: Try pushDefault
+    if let Some(push_default) = find_push_default(&contents) {
+        eprintln!("[falling back to pushDefault]: {push_default}");
+        if let Some(url) = find_remote_url(&contents, &push_default) {
+            return Some(url);
         }
+    }
 
-        // go up
-        if !dir.pop() { break }
+    // Fallback 2: Try current branch's remote
+    if let Some(branch_remote) = find_current_branch_remote(&repo.git_dir, &contents) {
+        eprintln!("[falling back to branch remote]: {branch_remote}");
+        if let Some(url) = find_remote_url(&contents, &branch_remote) {
+            return Some(url);
+        }
     }
+
+    // Fallback 3: Use any available remote
+    if let Some(url) = find_any_remote_url(&contents) {
+        eprintln!("[falling back to first available remote]");
+        return Some(url);
+    }
+
     None
 }
 
@@ -218,9 +270,9 @@ fn find_push_default(contents: &str) -> Option<String> {
 }
 
 #[must_use]
-fn find_current_branch_remote(dir: &Path, contents: &str) -> Option<String> {
-    // Read current branch from HEAD
-    let head = dir.join(".git/HEAD");
+fn find_current_branch_remote(git_dir: &Path, contents: &str) -> Option<String> {
+    // Read current branch from HEAD, every worktree has its own
+    let head = git_dir.join("HEAD");
     let head_contents = fs::read_to_string(head).ok()?;
     let branch_name = head_contents
         .strip_prefix("ref: refs/heads/")?
@@ -310,6 +362,26 @@ pub fn truncate_path(path: &str, line_number: u32, max_len: usize) -> Cow<'_, st
     ret.into()
 }
 
+/// Path of `upath` relative to the scanned directory `cwd`, with `/` separators
+#[must_use]
+pub fn relative_path(cwd: &Path, upath: &str) -> String {
+    let path = Path::new(upath);
+    let path = path.strip_prefix(cwd).unwrap_or(path);
+
+    path.components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[must_use]
+pub fn json_str_array(json: &serde_json::Value) -> Option<Box<[Box<str>]>> {
+    json.as_array()?.iter().map(|v| {
+        v.as_str().map(|s| string_into_boxed_str_norealloc(s.to_owned()))
+    }).collect()
+}
+
 macro_rules! make_spawn {
     (
         $rx_inner_ty: ty,
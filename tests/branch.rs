@@ -0,0 +1,80 @@
+mod common;
+
+use common::{TempDir, git, make_repo, stalkr};
+
+use std::fs;
+
+#[test]
+fn report_and_purge_push_their_branches_to_the_remote() {
+    let root = TempDir::new("branch");
+
+    let remote = root.path().join("remote.git");
+    git(root.path(), &["init", "-q", "--bare", "-b", "main", remote.to_str().unwrap()]);
+
+    let work = TempDir::new("branch-work");
+    make_repo(&work, remote.to_str().unwrap(), &[
+        ("src/a.rs", "// TODO: one\nfn f() {}\n// TODO: two\n")
+    ]);
+    git(work.path(), &["push", "-q", "origin", "main"]);
+
+    let out = stalkr(work.path(), &["--backend", "local", "report", "--branch", "todos"], &[], "a\n");
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+
+    // a commit per tag on the branch, on top of main
+    let log = git(&remote, &["log", "--format=%s", "main..todos"]);
+    assert_eq!(log.lines().collect::<Vec<_>>(), ["Add TODO(#2): two", "Add TODO(#1): one"]);
+    assert_eq!(
+        git(&remote, &["show", "todos:src/a.rs"]),
+        "// TODO(#1): one\nfn f() {}\n// TODO(#2): two"
+    );
+
+    // the work tree is left on the branch
+    assert_eq!(git(work.path(), &["rev-parse", "--abbrev-ref", "HEAD"]), "todos");
+
+    // close the first issue in the local tracker
+    let issue = work.path().join(".stalkr/issues/1.md");
+    let closed = fs::read_to_string(&issue).unwrap().replace("state: open", "state: closed");
+    fs::write(&issue, closed).unwrap();
+
+    let out = stalkr(work.path(), &["--backend", "local", "purge", "--branch", "cleanup"], &[], "a\n");
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+
+    let log = git(&remote, &["log", "--format=%s", "todos..cleanup"]);
+    assert_eq!(log.lines().count(), 1, "{log}");
+    assert!(log.contains("#1"), "{log}");
+    assert_eq!(
+        git(&remote, &["show", "cleanup:src/a.rs"]),
+        "fn f() {}\n// TODO(#2): two"
+    );
+
+    // nothing was pushed anywhere else
+    assert_eq!(git(&remote, &["rev-parse", "main"]), git(work.path(), &["rev-parse", "main"]));
+}
+
+#[test]
+fn a_run_that_commits_nothing_leaves_no_branch_behind() {
+    let root = TempDir::new("branch-empty");
+
+    let remote = root.path().join("remote.git");
+    git(root.path(), &["init", "-q", "--bare", "-b", "main", remote.to_str().unwrap()]);
+
+    let work = TempDir::new("branch-empty-work");
+    make_repo(&work, remote.to_str().unwrap(), &[("src/a.rs", "// TODO: one\n")]);
+    git(work.path(), &["push", "-q", "origin", "main"]);
+
+    // the file is skipped
+    let out = stalkr(work.path(), &["--backend", "local", "report", "--branch", "todos"], &[], "s\n");
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+
+    assert_eq!(git(work.path(), &["rev-parse", "--abbrev-ref", "HEAD"]), "main");
+    assert_eq!(git(work.path(), &["branch", "--list", "todos"]), "");
+    assert_eq!(git(&remote, &["branch", "--list", "todos"]), "");
+
+    // an existing branch is refused before anything is scanned
+    git(work.path(), &["branch", "todos"]);
+
+    let out = stalkr(work.path(), &["--backend", "local", "report", "--branch", "todos"], &[], "a\n");
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("already exists"));
+    assert_eq!(work.read("src/a.rs"), "// TODO: one\n");
+}
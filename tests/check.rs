@@ -0,0 +1,28 @@
+mod common;
+
+use common::{TempDir, make_repo, stalkr};
+
+#[test]
+fn baselined_todos_dont_count_toward_a_budget() {
+    let dir = TempDir::new("check-budget");
+    make_repo(&dir, "https://github.com/owner/repo.git", &[
+        ("src/a.rs", "// TODO: one\n// TODO: two\n// TODO(#3): three\n")
+    ]);
+
+    let out = stalkr(dir.path(), &["baseline", "update"], &[], "");
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+
+    // the tagged one counts, the two known ones don't
+    let out = stalkr(dir.path(), &["check", "--budget", "src=1"], &[], "");
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stdout));
+
+    let out = stalkr(dir.path(), &["check", "--budget", "src=1", "--no-baseline"], &[], "");
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout).contains("src/: error: 3 todoʼs, over the budget of 1"));
+
+    dir.write("src/b.rs", "// TODO(#4): four\n");
+
+    let out = stalkr(dir.path(), &["check", "--budget", "src=1"], &[], "");
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout).contains("src/: error: 2 todoʼs, over the budget of 1"));
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use common::{TempDir, make_repo, stalkr};
+
+fn local_issue(dir: &TempDir, n: u64, location: &str) {
+    dir.write(
+        &format!(".stalkr/issues/{n}.md"),
+        &format!("---\ntitle: \"issue {n}\"\nstate: open\nlocation: \"{location}\"\n---\n")
+    );
+}
+
+fn state(dir: &TempDir, n: u64) -> String {
+    let issue = dir.read(&format!(".stalkr/issues/{n}.md"));
+    issue.lines().find_map(|l| l.strip_prefix("state: ")).unwrap().to_owned()
+}
+
+#[test]
+fn only_issues_whose_todo_is_known_to_be_gone_are_closed() {
+    let dir = TempDir::new("close");
+    make_repo(&dir, "https://example.invalid/owner/proj.git", &[
+        ("a.rs", "fn a() {}\n"),
+        ("b.rs", "// FIXME(#2): two\n"),
+        ("c.rs", "// TODO(#5): five\n"),
+        ("ignored.rs", "// TODO(#4): four\n"),
+        (".stalkrignore", "ignored.rs\n")
+    ]);
+
+    // the TODO was removed from its file
+    local_issue(&dir, 1, "a.rs:1");
+    // still tagged, with a keyword that isn't looked for
+    local_issue(&dir, 2, "b.rs:1");
+    // its file was deleted
+    local_issue(&dir, 3, "gone.rs:1");
+    // its file isn't scanned
+    local_issue(&dir, 4, "ignored.rs:1");
+    // still tagged
+    local_issue(&dir, 5, "c.rs:1");
+
+    let out = stalkr(dir.path(), &["--backend", "local", "--keywords", "TODO", "close"], &[], "a\n");
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+
+    let states = (1..=5).map(|n| state(&dir, n)).collect::<Vec<_>>();
+    assert_eq!(states, ["closed", "open", "closed", "open", "open"]);
+
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("#2: still tagged in b.rs"), "{stderr}");
+    assert!(stderr.contains("#4: ignored.rs wasn't scanned"), "{stderr}");
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use common::{TempDir, git, make_repo, stalkr};
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+const A: &str = "// TODO: one\nfn f() {}\n// TODO: two\n";
+const B: &str = "# TODO: three\n";
+
+fn repo_committing(name: &str, strategy: &str) -> TempDir {
+    let dir = TempDir::new(name);
+    make_repo(&dir, "https://example.invalid/owner/repo.git", &[
+        (".stalkr.json", &format!(r#"{{ "commit": "{strategy}" }}"#)),
+        ("a.rs", A),
+        ("b.py", B)
+    ]);
+    dir
+}
+
+fn report(dir: &TempDir) -> (bool, String) {
+    let out = stalkr(dir.path(), &["--backend", "local", "report"], &[], "a\na\n");
+    (out.status.success(), String::from_utf8_lossy(&out.stderr).into_owned())
+}
+
+// messages of the commits stalkr made, oldest first
+fn commits(dir: &TempDir) -> Vec<String> {
+    let log = git(dir.path(), &["log", "--reverse", "--format=%B%x00", "HEAD"]);
+    log.split('\0').map(str::trim).filter(|m| !m.is_empty() && *m != "initial").map(str::to_owned).collect()
+}
+
+#[test]
+fn per_file_makes_a_commit_for_each_file_listing_its_tags() {
+    let dir = repo_committing("commit-per-file", "per-file");
+    let (ok, stderr) = report(&dir);
+    assert!(ok, "{stderr}");
+
+    // the files may be prompted for in any order
+    let mut commits = commits(&dir);
+    commits.sort();
+
+    // a single tag keeps its own message
+    assert_eq!(commits, [
+        "Add 2 issue tags to a.rs\n\n- Add TODO(#1): one\n- Add TODO(#2): two",
+        "Add TODO(#3): three"
+    ]);
+    assert_eq!(git(dir.path(), &["status", "--porcelain", "a.rs", "b.py"]), "");
+}
+
+#[test]
+fn per_run_makes_one_commit_listing_every_tag() {
+    let dir = repo_committing("commit-per-run", "per-run");
+    let (ok, stderr) = report(&dir);
+    assert!(ok, "{stderr}");
+
+    let commits = commits(&dir);
+    assert_eq!(commits.len(), 1);
+
+    let (subject, body) = commits[0].split_once("\n\n").unwrap();
+    assert_eq!(subject, "Add 3 issue tags");
+
+    let mut lines = body.lines().collect::<Vec<_>>();
+    lines.sort_unstable();
+    assert_eq!(lines, ["- a.rs: Add TODO(#1): one", "- a.rs: Add TODO(#2): two", "- b.py: Add TODO(#3): three"]);
+
+    assert_eq!(dir.read("a.rs"), "// TODO(#1): one\nfn f() {}\n// TODO(#2): two\n");
+    assert_eq!(git(dir.path(), &["status", "--porcelain", "a.rs", "b.py"]), "");
+}
+
+#[test]
+fn per_run_undoes_the_edits_if_the_commit_is_rejected() {
+    let dir = repo_committing("commit-rejected", "per-run");
+
+    let hook = dir.path().join(".git/hooks/pre-commit");
+    fs::write(&hook, "#!/bin/sh\necho rejected >&2\nexit 1\n").unwrap();
+    fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let (ok, stderr) = report(&dir);
+    assert!(!ok);
+    assert!(stderr.contains("git commit failed, so the edits were undone"), "{stderr}");
+
+    assert_eq!(git(dir.path(), &["log", "--format=%s"]), "initial");
+    assert_eq!(dir.read("a.rs"), A);
+    assert_eq!(dir.read("b.py"), B);
+    assert_eq!(git(dir.path(), &["status", "--porcelain", "a.rs", "b.py"]), "");
+}
@@ -0,0 +1,294 @@
+#![allow(dead_code)]
+
+use std::fs;
+use std::thread;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Directory under the system temp dir, removed on drop
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.subsec_nanos());
+        let n = COUNTER.fetch_add(1, Ordering::SeqCst);
+
+        let path = std::env::temp_dir().join(format!{
+            "stalkr-test-{name}-{pid}-{n}-{nanos}",
+            pid = std::process::id()
+        });
+
+        fs::create_dir_all(&path).unwrap();
+
+        Self(path.canonicalize().unwrap())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn write(&self, rel: &str, contents: &str) {
+        let path = self.0.join(rel);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(path, contents).unwrap();
+    }
+
+    pub fn read(&self, rel: &str) -> String {
+        fs::read_to_string(self.0.join(rel)).unwrap()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Runs git in `dir`, panicking if it fails. Returns: its trimmed stdout
+pub fn git(dir: &Path, args: &[&str]) -> String {
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .output()
+        .unwrap();
+
+    assert!{
+        out.status.success(),
+        "git {args:?} failed: {}",
+        String::from_utf8_lossy(&out.stderr)
+    };
+
+    String::from_utf8_lossy(&out.stdout).trim().to_owned()
+}
+
+/// A git repository with one commit of `files` and `remote` as its origin
+pub fn make_repo(dir: &TempDir, remote: &str, files: &[(&str, &str)]) {
+    let path = dir.path();
+
+    git(path, &["init", "-q", "-b", "main"]);
+    git(path, &["config", "user.name", "stalkr test"]);
+    git(path, &["config", "user.email", "test@stalkr.invalid"]);
+    git(path, &["config", "commit.gpgsign", "false"]);
+    git(path, &["remote", "add", "origin", remote]);
+
+    for (rel, contents) in files {
+        dir.write(rel, contents);
+    }
+
+    git(path, &["add", "-A"]);
+    git(path, &["commit", "-q", "-m", "initial"]);
+}
+
+/// Runs the stalkr binary in `dir` with `args`, feeding it `stdin`
+pub fn stalkr(dir: &Path, args: &[&str], envs: &[(&str, &str)], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_stalkr"))
+        .current_dir(dir)
+        .arg("-d")
+        .arg(dir)
+        .args(args)
+        .envs(envs.iter().copied())
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+
+    child.wait_with_output().unwrap()
+}
+
+/// Runs the stalkr binary in `dir` with `args`, calls `at_prompt` once it asks for a selection
+/// (i.e. the scan is done) and then answers with `stdin`
+pub fn stalkr_at_prompt(dir: &Path, args: &[&str], at_prompt: impl FnOnce(), stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_stalkr"))
+        .arg("-d")
+        .arg(dir)
+        .args(args)
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut stdout = child.stdout.take().unwrap();
+
+    let mut seen = Vec::new();
+    let mut buf = [0; 1024];
+    while !String::from_utf8_lossy(&seen).contains("selection") {
+        let n = stdout.read(&mut buf).unwrap();
+        assert!(n > 0, "stalkr exited before prompting: {}", String::from_utf8_lossy(&seen));
+        seen.extend_from_slice(&buf[..n]);
+    }
+
+    at_prompt();
+
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+
+    let mut out = child.wait_with_output().unwrap();
+
+    stdout.read_to_end(&mut seen).unwrap();
+    out.stdout = seen;
+    out
+}
+
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: String,
+    /// Path with the query, e.g. `/api/v4/projects/a%2Fb/issues?page=1`
+    pub target: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn path(&self) -> &str {
+        self.target.split_once('?').map_or(&self.target, |(path, _)| path)
+    }
+
+    pub fn query(&self, key: &str) -> Option<&str> {
+        self.target.split_once('?')?.1.split('&').find_map(|kv| {
+            let (k, v) = kv.split_once('=')?;
+            (k == key).then_some(v)
+        })
+    }
+
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).unwrap()
+    }
+}
+
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String
+}
+
+impl Response {
+    pub fn json(status: u16, body: &serde_json::Value) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".to_owned(), "application/json".to_owned())],
+            body: body.to_string()
+        }
+    }
+
+    pub fn status(status: u16) -> Self {
+        Self { status, headers: Vec::new(), body: String::new() }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+}
+
+type Handler = dyn Fn(&Request) -> Response + Send + Sync;
+
+/// HTTP/1.1 server on a random local port that answers every request with `handler`
+/// and records them. Lives as long as the test process.
+pub struct StubServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<Request>>>
+}
+
+impl StubServer {
+    pub fn start(handler: impl Fn(&Request) -> Response + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        let server_requests = Arc::clone(&requests);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                let requests = Arc::clone(&server_requests);
+                let handler = Arc::clone(&handler);
+                thread::spawn(move || serve(stream, &requests, &*handler));
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+}
+
+fn serve(stream: TcpStream, requests: &Mutex<Vec<Request>>, handler: &Handler) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+
+    // keep-alive: serve requests until the client hangs up
+    while let Some(rq) = read_request(&mut reader) {
+        requests.lock().unwrap_or_else(PoisonError::into_inner).push(rq.clone());
+
+        let r = handler(&rq);
+
+        let mut head = format!("HTTP/1.1 {status} Stub\r\nContent-Length: {len}\r\n", status = r.status, len = r.body.len());
+        for (name, value) in &r.headers {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        head.push_str("\r\n");
+
+        if writer.write_all(head.as_bytes()).and_then(|()| writer.write_all(r.body.as_bytes())).is_err() {
+            return
+        }
+    }
+}
+
+fn read_request(reader: &mut BufReader<TcpStream>) -> Option<Request> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).ok()? == 0 { return None }
+
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_owned();
+    let target = parts.next()?.to_owned();
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let line = line.trim_end();
+        if line.is_empty() { break }
+        let (name, value) = line.split_once(':')?;
+        headers.push((name.trim().to_owned(), value.trim().to_owned()));
+    }
+
+    let len = headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.parse().ok())
+        .unwrap_or(0);
+
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body).ok()?;
+
+    Some(Request { method, target, headers, body: String::from_utf8_lossy(&body).into_owned() })
+}
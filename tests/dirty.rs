@@ -0,0 +1,55 @@
+mod common;
+
+use common::{TempDir, git, make_repo, stalkr};
+
+#[test]
+fn stage_own_changes_tags_the_lines_the_todo_is_on_in_the_index_and_head() {
+    let dir = TempDir::new("dirty");
+    make_repo(&dir, "https://example.invalid/owner/repo.git", &[
+        ("a.rs", "// TODO: same\nx\nx\nx\n// TODO: same\n")
+    ]);
+
+    // a staged line on top
+    dir.write("a.rs", "staged\n// TODO: same\nx\nx\nx\n// TODO: same\n");
+    git(dir.path(), &["add", "a.rs"]);
+
+    // more lines on top and the second TODO done, so the first one is now on the line
+    // the second one is on in the index, which is where a patch made of the work tree goes
+    dir.write("a.rs", "n\nn\nn\nn\nstaged\n// TODO: same\nx\nx\nx\n// done\n");
+
+    let out = stalkr(dir.path(), &["--backend", "local", "report", "--stage-own-changes"], &[], "a\n");
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+
+    assert_eq!(git(dir.path(), &["log", "--format=%s", "-1"]), "Add TODO(#1): same");
+
+    // only the tag was committed, on the first TODO
+    assert_eq!(
+        git(dir.path(), &["show", "HEAD:a.rs"]),
+        "// TODO(#1): same\nx\nx\nx\n// TODO: same"
+    );
+
+    // the staged change stays staged, with the tag on top of it
+    assert_eq!(
+        git(dir.path(), &["show", ":a.rs"]),
+        "staged\n// TODO(#1): same\nx\nx\nx\n// TODO: same"
+    );
+
+    assert_eq!(dir.read("a.rs"), "n\nn\nn\nn\nstaged\n// TODO(#1): same\nx\nx\nx\n// done\n");
+}
+
+#[test]
+fn stage_own_changes_leaves_a_todo_that_is_part_of_other_changes_alone() {
+    let dir = TempDir::new("dirty-own");
+    make_repo(&dir, "https://example.invalid/owner/repo.git", &[
+        ("a.rs", "fn f() {}\n// TODO: old\n")
+    ]);
+
+    // the TODO itself is one of the uncommitted changes
+    dir.write("a.rs", "fn f() {}\n// TODO: new\n");
+
+    let out = stalkr(dir.path(), &["--backend", "local", "report", "--stage-own-changes"], &[], "a\n");
+    assert!(String::from_utf8_lossy(&out.stderr).contains("part of your uncommitted changes"));
+
+    assert_eq!(git(dir.path(), &["log", "--format=%s"]), "initial");
+    assert_eq!(dir.read("a.rs"), "fn f() {}\n// TODO: new\n");
+}
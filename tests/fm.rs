@@ -0,0 +1,70 @@
+mod common;
+
+use common::TempDir;
+
+use std::fs;
+
+use stalkr::fm::write_atomically;
+
+fn leftovers(dir: &TempDir) -> Vec<String> {
+    fs::read_dir(dir.path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".tmp"))
+        .collect()
+}
+
+#[test]
+fn replaces_the_contents() {
+    let dir = TempDir::new("fm-write");
+    dir.write("a.rs", "old\n");
+
+    write_atomically(&dir.path().join("a.rs"), b"new\n").unwrap();
+
+    assert_eq!(dir.read("a.rs"), "new\n");
+    assert!(leftovers(&dir).is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn keeps_the_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = TempDir::new("fm-perms");
+    dir.write("run.sh", "echo old\n");
+
+    let path = dir.path().join("run.sh");
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o750)).unwrap();
+
+    write_atomically(&path, b"echo new\n").unwrap();
+
+    assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o7777, 0o750);
+}
+
+#[cfg(unix)]
+#[test]
+fn keeps_a_symlink_and_replaces_its_target() {
+    let dir = TempDir::new("fm-symlink");
+    dir.write("real/a.rs", "old\n");
+
+    let link = dir.path().join("a.rs");
+    std::os::unix::fs::symlink(dir.path().join("real/a.rs"), &link).unwrap();
+
+    write_atomically(&link, b"new\n").unwrap();
+
+    assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+    assert_eq!(dir.read("real/a.rs"), "new\n");
+}
+
+#[test]
+fn removes_the_temp_file_when_it_fails() {
+    let dir = TempDir::new("fm-fail");
+    dir.write("sub/a.rs", "");
+
+    // a file can't be renamed over a directory that isn't empty
+    let e = write_atomically(&dir.path().join("sub"), b"new\n").unwrap_err();
+    assert_ne!(e.kind(), std::io::ErrorKind::NotFound, "{e}");
+
+    assert!(leftovers(&dir).is_empty(), "{:?}", leftovers(&dir));
+    assert!(dir.path().join("sub/a.rs").exists());
+}
@@ -0,0 +1,144 @@
+mod common;
+
+use common::{Response, StubServer};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use stalkr::http::Http;
+
+// answers the first `failing` requests with `fail`, every other one with 200
+fn server_failing_first(failing: usize, fail: fn() -> Response) -> StubServer {
+    let served = AtomicUsize::new(0);
+    StubServer::start(move |_| {
+        if served.fetch_add(1, Ordering::SeqCst) < failing {
+            fail()
+        } else {
+            Response::json(200, &serde_json::json!({ "ok": true }))
+        }
+    })
+}
+
+async fn get(http: &Http, url: &str) -> anyhow::Result<u16> {
+    let r = http.send(|client| Ok(client.get(url))).await?;
+    Ok(r.status().into())
+}
+
+#[tokio::test]
+async fn retries_a_rate_limited_request_until_it_succeeds() {
+    let server = server_failing_first(2, || Response::status(429).with_header("Retry-After", "0"));
+    let http = Http::new(surf::Client::new());
+
+    assert_eq!(get(&http, &server.url).await.unwrap(), 200);
+    assert_eq!(server.requests().len(), 3);
+    assert!(!http.is_aborted());
+}
+
+#[tokio::test]
+async fn retries_a_server_error_until_it_succeeds() {
+    let server = server_failing_first(1, || Response::status(503));
+    let http = Http::new(surf::Client::new());
+
+    assert_eq!(get(&http, &server.url).await.unwrap(), 200);
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn waits_for_the_rate_limit_reset_of_an_exhausted_window() {
+    let server = server_failing_first(1, || {
+        Response::status(403)
+            .with_header("X-RateLimit-Remaining", "0")
+            .with_header("X-RateLimit-Reset", "0")
+    });
+    let http = Http::new(surf::Client::new());
+
+    assert_eq!(get(&http, &server.url).await.unwrap(), 200);
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn aborts_after_too_many_failed_requests() {
+    let server = StubServer::start(|_| Response::status(429).with_header("Retry-After", "0"));
+    let http = Http::new(surf::Client::new());
+
+    // every request gives up after its attempts, the run is aborted after 3 of them
+    for _ in 0..3 {
+        let e = get(&http, &server.url).await.unwrap_err();
+        assert!(e.to_string().contains("rate limited"), "{e}");
+    }
+
+    assert!(http.is_aborted());
+    assert_eq!(server.requests().len(), 3 * 5);
+
+    // later requests don't even go out
+    let e = get(&http, &server.url).await.unwrap_err();
+    assert!(e.to_string().contains("aborted"), "{e}");
+    assert_eq!(server.requests().len(), 3 * 5);
+}
+
+#[tokio::test]
+async fn a_success_resets_the_failures() {
+    let server = StubServer::start(|rq| {
+        if rq.target == "/ok" {
+            Response::status(200)
+        } else {
+            Response::status(429).with_header("Retry-After", "0")
+        }
+    });
+    let http = Http::new(surf::Client::new());
+
+    let failing_url = format!("{}/fail", server.url);
+    let ok_url = format!("{}/ok", server.url);
+
+    // two requests fail for good, then one succeeds, then two more fail
+    get(&http, &failing_url).await.unwrap_err();
+    get(&http, &failing_url).await.unwrap_err();
+    get(&http, &ok_url).await.unwrap();
+    get(&http, &failing_url).await.unwrap_err();
+    get(&http, &failing_url).await.unwrap_err();
+
+    assert!(!http.is_aborted());
+}
+
+#[tokio::test]
+async fn a_secondary_rate_limit_403_is_retried() {
+    let server = server_failing_first(1, || Response::status(403).with_header("Retry-After", "0"));
+    let http = Http::new(surf::Client::new());
+
+    assert_eq!(get(&http, &server.url).await.unwrap(), 200);
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn a_plain_403_is_returned_as_is() {
+    let server = server_failing_first(1, || Response::status(403));
+    let http = Http::new(surf::Client::new());
+
+    assert_eq!(get(&http, &server.url).await.unwrap(), 403);
+    assert_eq!(server.requests().len(), 1);
+    assert!(!http.is_aborted());
+}
+
+#[tokio::test]
+async fn a_post_is_not_sent_again_after_a_server_error() {
+    let server = server_failing_first(1, || Response::status(500));
+    let http = Http::new(surf::Client::new());
+
+    let url = format!("{}/issues", server.url);
+    let e = http.send(|client| Ok(client.post(&url).body_string("{}".to_owned()))).await.unwrap_err();
+    assert!(e.to_string().contains("server error"), "{e}");
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].method, "POST");
+}
+
+#[tokio::test]
+async fn a_rate_limited_post_is_retried() {
+    let server = server_failing_first(1, || Response::status(429).with_header("Retry-After", "0"));
+    let http = Http::new(surf::Client::new());
+
+    let url = format!("{}/issues", server.url);
+    let r = http.send(|client| Ok(client.post(&url).body_string("{}".to_owned()))).await.unwrap();
+    assert_eq!(u16::from(r.status()), 200);
+    assert_eq!(server.requests().len(), 2);
+}
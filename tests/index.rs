@@ -0,0 +1,32 @@
+use stalkr::index::{self, IssueIndex, OpenIssue};
+
+fn issue(number: u64, title: &str, location: &str) -> OpenIssue {
+    let body = format!("body\n\n{}", index::make_location_marker(location));
+    OpenIssue::new(number, title, Some(&body), None)
+}
+
+#[test]
+fn an_issue_filed_from_the_same_file_is_found_after_its_todo_moved() {
+    let index = IssueIndex::new(vec![issue(1, "fix it", "src/a.rs:10")]);
+
+    assert_eq!(index.resolve(&[("fix it", "src/a.rs:12")]), [Some(1)]);
+    assert_eq!(index.resolve(&[("fix it", "src/b.rs:10")]), [None]);
+}
+
+#[test]
+fn todos_with_the_same_title_dont_take_each_others_issue() {
+    let index = IssueIndex::new(vec![issue(1, "handle errors", "src/a.rs:10")]);
+
+    // the one at the exact location keeps its issue, the other one gets a new one
+    let found = index.resolve(&[("handle errors", "src/a.rs:5"), ("handle errors", "src/a.rs:10")]);
+    assert_eq!(found, [None, Some(1)]);
+
+    // both moved, there's no telling which one it was filed for
+    let found = index.resolve(&[("handle errors", "src/a.rs:12"), ("handle errors", "src/a.rs:20")]);
+    assert_eq!(found, [None, None]);
+
+    // two issues left to choose from
+    let index = IssueIndex::new(vec![issue(1, "handle errors", "src/a.rs:10"), issue(2, "handle errors", "src/a.rs:20")]);
+    assert_eq!(index.resolve(&[("handle errors", "src/a.rs:30")]), [None]);
+    assert_eq!(index.resolve(&[("handle errors", "src/a.rs:20"), ("handle errors", "src/a.rs:30")]), [Some(2), Some(1)]);
+}
@@ -0,0 +1,54 @@
+use stalkr::todo::Todo;
+use stalkr::meta::Meta;
+use stalkr::keyword::Keywords;
+
+#[test]
+fn keywords_may_be_followed_by_spaces_before_their_delimiter() {
+    let keywords = Keywords::default();
+
+    for s in ["TODO: fix", "TODO : fix", "TODO\t: fix", "TODO (#1): fix", "TODO [bug]: fix"] {
+        assert!(keywords.match_start(s).is_some(), "{s:?}");
+    }
+
+    for s in ["TODO fix", "TODOS: fix", "TODO"] {
+        assert!(keywords.match_start(s).is_none(), "{s:?}");
+    }
+}
+
+#[test]
+fn spaces_before_the_delimiter_are_part_of_what_the_tag_replaces() {
+    let head = Todo::extract_todo_head("TODO : fix it", "TODO").unwrap();
+    assert_eq!(head.title, "fix it");
+    assert!(!head.is_tagged);
+    assert_eq!(head.groups_len, " ".len());
+
+    let head = Todo::extract_todo_head("TODO (#12) : fix it", "TODO").unwrap();
+    assert_eq!(head.title, "fix it");
+    assert_eq!(head.issue_number, Some(12));
+    assert_eq!(head.groups_len, " (#12) ".len());
+
+    assert!(Todo::extract_todo_head("TODO (maybe) later", "TODO").is_none());
+}
+
+#[test]
+fn bare_words_in_parens_are_a_tag_not_labels() {
+    // the owner convention, a malformed tag as ever
+    let head = Todo::extract_todo_head("TODO(alice): fix it", "TODO").unwrap();
+    assert!(head.is_tagged);
+    assert_eq!(head.issue_number, None);
+    assert_eq!(head.meta, Meta::default());
+
+    // with an assignee or a key, the bare words are labels
+    let head = Todo::extract_todo_head("TODO(@alice, bug, P1): fix it", "TODO").unwrap();
+    assert!(!head.is_tagged);
+    assert_eq!(head.meta, Meta::parse("@alice, bug, P1").unwrap());
+
+    let head = Todo::extract_todo_head("TODO(milestone=v1, bug): fix it", "TODO").unwrap();
+    assert!(!head.is_tagged);
+    assert_eq!(head.meta, Meta::parse("bug, milestone=v1").unwrap());
+
+    // in brackets there's nothing else they could be
+    let head = Todo::extract_todo_head("TODO[bug]: fix it", "TODO").unwrap();
+    assert!(!head.is_tagged);
+    assert_eq!(head.meta, Meta::parse("label=bug").unwrap());
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use common::{TempDir, git, make_repo, stalkr_at_prompt};
+
+// reports the TODO's of `contents`, changing the file to `changed` while the prompt waits
+fn report_changed_during_prompt(contents: &str, changed: &str) -> (TempDir, String) {
+    let dir = TempDir::new("reanchor");
+    make_repo(&dir, "https://example.invalid/owner/proj.git", &[("a.rs", contents)]);
+
+    let out = stalkr_at_prompt(
+        dir.path(),
+        &["--backend", "local", "report", "--allow-dirty"],
+        || dir.write("a.rs", changed),
+        "a\n"
+    );
+
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+
+    (dir, String::from_utf8_lossy(&out.stderr).into_owned())
+}
+
+#[test]
+fn a_todo_that_moved_since_the_scan_is_tagged_where_it_is_now() {
+    let (dir, stderr) = report_changed_during_prompt(
+        "fn f() {}\n// TODO: one\n",
+        "use std::io;\n\nfn f() {}\n// TODO: one\n"
+    );
+
+    assert_eq!(dir.read("a.rs"), "use std::io;\n\nfn f() {}\n// TODO(#1): one\n");
+    assert!(stderr.contains("found again by their lines"), "{stderr}");
+    assert_eq!(git(dir.path(), &["status", "--porcelain", "a.rs"]), "");
+}
+
+#[test]
+fn a_todo_that_was_changed_since_the_scan_leaves_the_file_alone() {
+    let changed = "fn f() {}\n// TODO: one, reworded\n";
+    let (dir, stderr) = report_changed_during_prompt("fn f() {}\n// TODO: one\n", changed);
+
+    assert_eq!(dir.read("a.rs"), changed);
+    assert!(stderr.contains("can't be found in it anymore, leaving the file alone"), "{stderr}");
+}
+
+#[test]
+fn a_todo_that_cant_be_told_apart_anymore_leaves_the_file_alone() {
+    // the line is now just as far above where it was as below it
+    let changed = "// TODO: one\nlet x = 100;\n// TODO: one\n";
+    let (dir, stderr) = report_changed_during_prompt("let x = 100;\n// TODO: one\n", changed);
+
+    assert_eq!(dir.read("a.rs"), changed);
+    assert!(stderr.contains("is in it more than once now, leaving the file alone"), "{stderr}");
+}
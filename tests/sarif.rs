@@ -0,0 +1,98 @@
+mod common;
+
+use common::{TempDir, make_repo, stalkr};
+
+use serde_json::Value;
+
+// the objects of the OASIS SARIF 2.1.0 schema that stalkr emits
+const SCHEMA: &str = include_str!("fixtures/sarif-schema-2.1.0.json");
+
+fn validate(log: &Value) {
+    let schema: Value = serde_json::from_str(SCHEMA).unwrap();
+
+    let validator = jsonschema::options()
+        .should_validate_formats(true)
+        .build(&schema)
+        .unwrap();
+
+    let errors = validator.iter_errors(log).map(|e| format!("{e} at {}", e.instance_path)).collect::<Vec<_>>();
+    assert!(errors.is_empty(), "not a valid SARIF 2.1.0 log:\n{}", errors.join("\n"));
+}
+
+#[test]
+fn list_as_sarif_is_a_valid_sarif_2_1_0_log() {
+    let dir = TempDir::new("sarif");
+
+    make_repo(&dir, "https://github.com/owner/repo.git", &[
+        ("src/main.rs", "\
+fn main() {}
+
+// TODO: first thing
+//   more about it
+fn f() {} // FIXME(#7): already tracked
+"),
+        ("docs/a file.py", "# TODO: spaces in the path\n")
+    ]);
+
+    let out = stalkr(
+        dir.path(),
+        &["--keywords", "TODO,FIXME", "list", "--format", "sarif"],
+        &[],
+        ""
+    );
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+
+    let log: Value = serde_json::from_slice(&out.stdout).unwrap();
+
+    validate(&log);
+
+    // required top-level properties
+    assert_eq!(log["version"], "2.1.0");
+    assert_eq!(log["$schema"], "https://json.schemastore.org/sarif-2.1.0.json");
+
+    let runs = log["runs"].as_array().unwrap();
+    assert_eq!(runs.len(), 1);
+
+    let run = &runs[0];
+    let driver = &run["tool"]["driver"];
+    assert_eq!(driver["name"], "stalkr");
+
+    let rules = driver["rules"].as_array().unwrap();
+    let mut rule_ids = rules.iter().map(|r| r["id"].as_str().unwrap()).collect::<Vec<_>>();
+    rule_ids.sort_unstable();
+    assert_eq!(rule_ids, ["FIXME", "TODO"]);
+
+    let mut results = run["results"].as_array().unwrap().iter().map(|result| {
+        // the title, followed by the lines of the description
+        let message = result["message"]["text"].as_str().unwrap();
+
+        // a result's ruleIndex points at its rule
+        let rule_index = result["ruleIndex"].as_u64().unwrap() as usize;
+        assert_eq!(rules[rule_index]["id"], result["ruleId"]);
+
+        let level = result["level"].as_str().unwrap();
+        assert!(["none", "note", "warning", "error"].contains(&level));
+
+        let locations = result["locations"].as_array().unwrap();
+        assert_eq!(locations.len(), 1);
+
+        let location = &locations[0]["physicalLocation"];
+        let uri = location["artifactLocation"]["uri"].as_str().unwrap().to_owned();
+
+        // lines are 1-based
+        let line = location["region"]["startLine"].as_u64().unwrap();
+        assert!(line >= 1);
+
+        let issue_number = result["properties"]["issueNumber"].as_u64();
+
+        (uri, line, result["ruleId"].as_str().unwrap().to_owned(), level.to_owned(), message.to_owned(), issue_number)
+    }).collect::<Vec<_>>();
+
+    results.sort();
+
+    assert_eq!(results, [
+        ("docs/a%20file.py".to_owned(), 1, "TODO".to_owned(), "warning".to_owned(), "spaces in the path".to_owned(), None),
+        ("src/main.rs".to_owned(), 3, "TODO".to_owned(), "warning".to_owned(), "first thing\nmore about it".to_owned(), None),
+        ("src/main.rs".to_owned(), 5, "FIXME".to_owned(), "note".to_owned(), "already tracked".to_owned(), Some(7)),
+    ]);
+}
@@ -0,0 +1,25 @@
+use stalkr::syntax::Syntax;
+use stalkr::comment::Comment;
+
+#[test]
+fn word_markers_only_start_a_comment_as_a_token_of_their_own() {
+    for line in ["REM TODO: fix", "  rem TODO: fix", "echo hi & REM TODO: fix", "@REM TODO: fix", "REM"] {
+        assert!(Syntax::BATCH.find_comment_start(line.as_bytes()).is_some(), "{line:?}");
+    }
+
+    for line in ["set REMOTE=origin", "set remaining=1", "call :xrem TODO", "echo _rem TODO", "echo REM:"] {
+        assert!(Syntax::BATCH.find_comment_start(line.as_bytes()).is_none(), "{line:?}");
+    }
+
+    let (at, _) = Syntax::VB.find_comment_start(b"Dim remote = 1 ' TODO: fix").unwrap();
+    assert_eq!(at, "Dim remote = 1 ".len());
+}
+
+#[test]
+fn description_lines_need_a_word_marker_of_their_own() {
+    let rem = Comment::Line("REM");
+
+    assert_eq!(rem.is_line_a_comment("  REM more"), Some("  REM".len()));
+    assert_eq!(rem.is_line_a_comment("REM\n"), Some("REM".len()));
+    assert_eq!(rem.is_line_a_comment("REMOTE=1"), None);
+}
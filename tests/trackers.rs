@@ -0,0 +1,164 @@
+mod common;
+
+use common::{Request, Response, StubServer, TempDir, git, make_repo, stalkr};
+
+use serde_json::{Value, json};
+
+// `count` open issues numbered from `first`, in the form both GitLab and Gitea list them
+fn open_issues(first: u64, count: u64, number_key: &str) -> Value {
+    (first..first + count).map(|n| json!({
+        number_key: n,
+        "title": format!("unrelated issue {n}"),
+        "body": "",
+        "description": "",
+        "labels": []
+    })).collect()
+}
+
+fn page(rq: &Request) -> u64 {
+    rq.query("page").and_then(|p| p.parse().ok()).unwrap_or(1)
+}
+
+#[test]
+fn gitlab_files_an_issue_and_tags_the_todo() {
+    let server = StubServer::start(|rq| match (rq.method.as_str(), rq.path()) {
+        ("GET", "/api/v4/projects/group%2Fsub%2Fproj/issues") => match page(rq) {
+            1 => Response::json(200, &open_issues(1, 100, "iid")),
+            _ => Response::json(200, &open_issues(101, 1, "iid"))
+        },
+
+        ("POST", "/api/v4/projects/group%2Fsub%2Fproj/issues") => Response::json(201, &json!({ "iid": 142 })),
+
+        _ => Response::status(404)
+    });
+
+    let dir = TempDir::new("gitlab");
+    make_repo(&dir, &format!("{}/group/sub/proj.git", server.url), &[
+        (".stalkr.json", &format!(r#"{{ "gitlab": {{ "url": "{}" }} }}"#, server.url)),
+        ("src/lib.rs", "// TODO: make it faster\nfn f() {}\n")
+    ]);
+
+    let out = stalkr(
+        dir.path(),
+        &["report"],
+        &[("STALKR_GITLAB_TOKEN", "gl-secret")],
+        "a\n"
+    );
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+
+    let requests = server.requests();
+
+    // every request authenticates with the token
+    for rq in &requests {
+        assert_eq!(rq.header("PRIVATE-TOKEN"), Some("gl-secret"), "{} {}", rq.method, rq.target);
+        assert_eq!(rq.header("Authorization"), None);
+    }
+
+    // a full page means there may be more, a short one is the last
+    let pages = requests.iter()
+        .filter(|rq| rq.method == "GET")
+        .map(|rq| (rq.query("state"), rq.query("per_page"), page(rq)))
+        .collect::<Vec<_>>();
+    assert_eq!(pages, [(Some("opened"), Some("100"), 1), (Some("opened"), Some("100"), 2)]);
+
+    let posts = requests.iter().filter(|rq| rq.method == "POST").collect::<Vec<_>>();
+    assert_eq!(posts.len(), 1);
+
+    let body = posts[0].json();
+    assert_eq!(body["title"], "make it faster");
+    assert!(body["description"].as_str().unwrap().contains("src/lib.rs:1"));
+    assert!(body.get("body").is_none());
+
+    assert_eq!(dir.read("src/lib.rs"), "// TODO(#142): make it faster\nfn f() {}\n");
+    assert_eq!(git(dir.path(), &["status", "--porcelain"]), "");
+}
+
+#[test]
+fn gitea_files_an_issue_with_label_ids_and_tags_the_todo() {
+    let server = StubServer::start(|rq| match (rq.method.as_str(), rq.path()) {
+        ("GET", "/api/v1/repos/owner/proj/issues") => match page(rq) {
+            1 => Response::json(200, &open_issues(1, 50, "number")),
+            _ => Response::json(200, &json!([]))
+        },
+
+        ("GET", "/api/v1/repos/owner/proj/labels") => Response::json(200, &json!([
+            { "id": 3, "name": "bug" }
+        ])),
+
+        ("POST", "/api/v1/repos/owner/proj/labels") => Response::json(201, &json!({ "id": 9 })),
+
+        ("POST", "/api/v1/repos/owner/proj/issues") => Response::json(201, &json!({ "number": 51 })),
+
+        _ => Response::status(404)
+    });
+
+    let dir = TempDir::new("gitea");
+    make_repo(&dir, &format!("{}/owner/proj.git", server.url), &[
+        ("main.py", "x = 1\n# TODO[bug, perf]: cache it\n")
+    ]);
+
+    let out = stalkr(
+        dir.path(),
+        &["--backend", "gitea", "report"],
+        &[("STALKR_GITEA_TOKEN", "gt-secret")],
+        "a\n"
+    );
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+
+    let requests = server.requests();
+
+    for rq in &requests {
+        assert_eq!(rq.header("Authorization"), Some("token gt-secret"), "{} {}", rq.method, rq.target);
+    }
+
+    let issue_pages = requests.iter()
+        .filter(|rq| rq.method == "GET" && rq.path().ends_with("/issues"))
+        .map(|rq| (rq.query("state"), rq.query("limit"), page(rq)))
+        .collect::<Vec<_>>();
+    assert_eq!(issue_pages, [(Some("open"), Some("50"), 1), (Some("open"), Some("50"), 2)]);
+
+    // the missing label is created, the existing one is looked up
+    let created_labels = requests.iter()
+        .filter(|rq| rq.method == "POST" && rq.path().ends_with("/labels"))
+        .map(|rq| rq.json()["name"].clone())
+        .collect::<Vec<_>>();
+    assert_eq!(created_labels, ["perf"]);
+
+    let issue = requests.iter()
+        .find(|rq| rq.method == "POST" && rq.path().ends_with("/issues"))
+        .unwrap()
+        .json();
+    assert_eq!(issue["title"], "cache it");
+    assert_eq!(issue["labels"], json!([3, 9]));
+
+    assert_eq!(dir.read("main.py"), "x = 1\n# TODO(#51): cache it\n");
+}
+
+#[test]
+fn gitea_looks_up_only_the_referenced_issues_before_purging() {
+    let server = StubServer::start(|rq| match (rq.method.as_str(), rq.path()) {
+        ("GET", "/api/v1/repos/owner/proj/issues/1") => Response::json(200, &json!({ "number": 1, "state": "closed" })),
+        ("GET", "/api/v1/repos/owner/proj/issues/2") => Response::json(200, &json!({ "number": 2, "state": "open" })),
+        _ => Response::status(404)
+    });
+
+    let dir = TempDir::new("gitea-purge");
+    make_repo(&dir, &format!("{}/owner/proj.git", server.url), &[
+        ("main.py", "# TODO(#1): one\n# TODO(#2): two\n# TODO(#3): three\n")
+    ]);
+
+    let out = stalkr(
+        dir.path(),
+        &["--backend", "gitea", "purge"],
+        &[("STALKR_GITEA_TOKEN", "gt-secret")],
+        "a\n"
+    );
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+
+    // nothing is listed, and the issue that couldn't be found is kept rather than taken as open
+    let requests = server.requests();
+    assert!(requests.iter().all(|rq| rq.method == "GET" && rq.path() != "/api/v1/repos/owner/proj/issues"));
+    assert_eq!(requests.iter().filter(|rq| rq.path().ends_with("/issues/3")).count(), 2);
+
+    assert_eq!(dir.read("main.py"), "# TODO(#2): two\n# TODO(#3): three\n");
+}